@@ -0,0 +1,169 @@
+use crate::region::{parse_bed, Target};
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// A feature such as a gene or exon, drawn as a bar under the tracks
+#[derive(Debug, Clone)]
+pub struct Feature {
+    /// 0-based start
+    pub start: u32,
+    /// exclusive end
+    pub end: u32,
+    pub name: Option<String>,
+    /// exon-like, drawn as a thick bar over the thin line of its gene or transcript
+    pub thick: bool,
+}
+
+/// Features of a BED or GFF/GTF file overlapping 0-based `start` to exclusive `end` of `chrom`.
+///
+/// The format is taken from the extension, anything but `.gff`, `.gff3` or `.gtf` is read as BED.
+pub fn load_features(path: &str, chrom: &str, start: u32, end: u32) -> Result<Vec<Feature>> {
+    Ok(read_features(path, Some(chrom))?
+        .into_iter()
+        .map(|(_, feature)| feature)
+        .filter(|feature| feature.start < end && feature.end > start)
+        .collect())
+}
+
+/// Span of the features named `name` in a BED or GFF/GTF file, e.g. all exons of a gene.
+///
+/// The span covers every feature of that name, which must all be on one contig.
+pub fn find_feature(path: &str, name: &str) -> Result<Target> {
+    let mut span: Option<Target> = None;
+    let mut contigs: Vec<String> = Vec::new();
+    for (chrom, feature) in read_features(path, None)? {
+        if feature.name.as_deref() != Some(name) {
+            continue;
+        }
+        if !contigs.contains(&chrom) {
+            contigs.push(chrom.clone());
+        }
+        match span.as_mut() {
+            Some(span) => {
+                span.start = span.start.min(feature.start);
+                span.end = span.end.max(feature.end);
+            }
+            None => {
+                span = Some(Target {
+                    chrom,
+                    start: feature.start,
+                    end: feature.end,
+                    name: Some(name.to_string()),
+                })
+            }
+        }
+    }
+    if contigs.len() > 1 {
+        return Err(anyhow!(
+            "{} is on several contigs in {}: {}, give its coordinates instead",
+            name,
+            path,
+            contigs.join(", ")
+        ));
+    }
+    span.ok_or_else(|| anyhow!("no feature named {} in {}", name, path))
+}
+
+// features of a BED or GFF/GTF file with their contig, of one contig only if given
+fn read_features(path: &str, chrom: Option<&str>) -> Result<Vec<(String, Feature)>> {
+    let lower = path.to_ascii_lowercase();
+    if [".gff", ".gff3", ".gtf"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+    {
+        return parse_gff(path, chrom);
+    }
+    Ok(parse_bed(path)?
+        .into_iter()
+        .filter(|target| chrom.is_none_or(|chrom| target.chrom == chrom))
+        .map(|target| {
+            let feature = Feature {
+                start: target.start,
+                end: target.end,
+                name: target.name,
+                thick: true,
+            };
+            (target.chrom, feature)
+        })
+        .collect())
+}
+
+// features in a GFF3 or GTF file, of one contig only if given, converted to 0-based
+// half-open coordinates
+fn parse_gff(path: &str, chrom: Option<&str>) -> Result<Vec<(String, Feature)>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut features = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = i + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(anyhow!(
+                "{}:{}: expected 9 columns, found {}",
+                path,
+                line_no,
+                fields.len()
+            ));
+        }
+        if chrom.is_some_and(|chrom| fields[0] != chrom) {
+            continue;
+        }
+        let parse_coord = |coord: &str| {
+            coord
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| anyhow!("{}:{}: bad coordinate `{}` ({})", path, line_no, coord, e))
+        };
+        let start = parse_coord(fields[3])?;
+        let end = parse_coord(fields[4])?;
+        if start == 0 || start > end {
+            return Err(anyhow!(
+                "{}:{}: invalid feature span {}-{}",
+                path,
+                line_no,
+                start,
+                end
+            ));
+        }
+        let feature = Feature {
+            start: start - 1,
+            end,
+            name: gff_name(fields[8]),
+            thick: !matches!(
+                fields[2],
+                "gene" | "mRNA" | "transcript" | "pseudogene" | "ncRNA_gene"
+            ),
+        };
+        features.push((fields[0].to_string(), feature));
+    }
+    Ok(features)
+}
+
+// the most readable name among the attributes, GFF3 `key=value` or GTF `key "value"`
+fn gff_name(attributes: &str) -> Option<String> {
+    let pairs: Vec<(&str, &str)> = attributes
+        .split(';')
+        .filter_map(|pair| {
+            let pair = pair.trim();
+            pair.split_once('=')
+                .or_else(|| pair.split_once(' '))
+                .map(|(key, value)| (key.trim(), value.trim().trim_matches('"')))
+        })
+        .collect();
+    [
+        "Name",
+        "gene_name",
+        "transcript_name",
+        "gene",
+        "ID",
+        "gene_id",
+    ]
+    .iter()
+    .find_map(|key| pairs.iter().find(|(k, _)| k == key))
+    .map(|(_, value)| value.to_string())
+    .filter(|value| !value.is_empty())
+}
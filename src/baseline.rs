@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Expected depth at each position of 0-based `start` to exclusive `end` of `chrom`, from a
+/// bedGraph such as the mean coverage of a panel of normals.
+///
+/// Positions no interval covers are 0, where a ratio to the baseline is undefined.
+/// Blank, comment, `track` and `browser` lines are skipped; errors report the line number.
+pub fn load_baseline(path: &str, chrom: &str, start: u32, end: u32) -> Result<Vec<f64>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut expected = vec![0.0; end.saturating_sub(start) as usize];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = i + 1;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 4 {
+            return Err(anyhow!(
+                "{}:{}: expected 4 columns, found {}",
+                path,
+                line_no,
+                fields.len()
+            ));
+        }
+        if fields[0] != chrom {
+            continue;
+        }
+        let parse_coord = |coord: &str| {
+            coord
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| anyhow!("{}:{}: bad coordinate `{}` ({})", path, line_no, coord, e))
+        };
+        let from = parse_coord(fields[1])?.max(start);
+        let to = parse_coord(fields[2])?.min(end);
+        if from >= to {
+            continue;
+        }
+        let depth = fields[3]
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|depth| depth.is_finite() && *depth >= 0.0)
+            .ok_or_else(|| anyhow!("{}:{}: bad depth `{}`", path, line_no, fields[3]))?;
+        expected[(from - start) as usize..(to - start) as usize].fill(depth);
+    }
+    Ok(expected)
+}
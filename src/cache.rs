@@ -0,0 +1,292 @@
+use crate::cov::{BedGraph, Binning, Position, RegionDepths, RegionProcessor, MAPQ_STEPS};
+use crate::stats::DepthHistogram;
+use anyhow::{anyhow, Result};
+use rust_htslib::bam;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// First bytes of a cache file, bumped whenever the layout changes
+const MAGIC: &[u8; 8] = b"TCOVDP04";
+
+/// Depths of a region saved under a directory, so reopening the same locus skips the reads.
+///
+/// A file is keyed by the input path and modification time, the region and the settings that
+/// change the depths, binning included, so editing or replacing the input invalidates it. Each
+/// file holds the magic bytes, the number of bins, the positions per bin, the number of reads
+/// counted and a mask of the tracks counted with the depth as little-endian `u64`s, then one
+/// little-endian `u32` depth per bin followed by the values of each track in the mask, in the
+/// order of the mask bits. The depth histogram and the low-depth runs close the file, each as a
+/// `u64` count followed by its `u64` tallies or `u32` start and end pairs.
+pub struct CachedProcessor {
+    inner: Box<dyn RegionProcessor + Sync>,
+    reads: PathBuf,
+    dir: PathBuf,
+    /// everything besides the input and region that the depths depend on
+    settings: String,
+}
+
+impl CachedProcessor {
+    /// Cache the depths of `inner` over `reads` in `dir`, created on the first write
+    pub fn new(
+        inner: Box<dyn RegionProcessor + Sync>,
+        reads: PathBuf,
+        dir: PathBuf,
+        settings: String,
+    ) -> Self {
+        Self {
+            inner,
+            reads,
+            dir,
+            settings,
+        }
+    }
+
+    // cache file of a region, changing with the input's modification time; the key is hashed
+    // with FNV-1a, which unlike the std hasher gives the same name across builds
+    fn path(&self, tid: &str, start: u32, stop: u32, binning: Binning) -> Result<PathBuf> {
+        let modified = fs::metadata(&self.reads)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)?
+            .as_nanos();
+        let key = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:?}",
+            fs::canonicalize(&self.reads)?.display(),
+            modified,
+            tid,
+            start,
+            stop,
+            self.settings,
+            binning
+        );
+        Ok(self
+            .dir
+            .join(format!("{:016x}.depth", fnv1a(key.as_bytes()))))
+    }
+
+    // depths and tracks of a cache file, none if it is missing or not a complete cache file
+    fn load(path: &Path, tid: &str, start: u32) -> Option<RegionDepths> {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).ok()?;
+        if &magic != MAGIC {
+            return None;
+        }
+        let len = usize::try_from(read_u64(&mut reader)?).ok()?;
+        let bin_size = u32::try_from(read_u64(&mut reader)?).ok()?;
+        let reads = read_u64(&mut reader)?;
+        let mask = read_u64(&mut reader)?;
+        let depths = read_track(&mut reader, len)?;
+        let mut track = |bit: u64| {
+            if mask & bit == 0 {
+                return Some(None);
+            }
+            read_track(&mut reader, len).map(Some)
+        };
+        let reverse = track(REVERSE_TRACK)?;
+        let mapq = track(MAPQ_TRACK)?;
+        let softclips = track(SOFTCLIP_TRACK)?;
+        let hints = track(HINT_TRACK)?;
+        let usable = if mask & USABLE_TRACK == 0 {
+            None
+        } else {
+            Some(
+                (0..MAPQ_STEPS.len())
+                    .map(|_| read_track(&mut reader, len))
+                    .collect::<Option<Vec<_>>>()?,
+            )
+        };
+        let weighted = if mask & WEIGHTED_TRACK == 0 {
+            None
+        } else {
+            let bits = read_track(&mut reader, len)?;
+            Some(bits.into_iter().map(f32::from_bits).collect())
+        };
+        let depths_seen = usize::try_from(read_u64(&mut reader)?).ok()?;
+        let histogram = (0..depths_seen)
+            .map(|_| read_u64(&mut reader))
+            .collect::<Option<Vec<_>>>()?;
+        let runs = usize::try_from(read_u64(&mut reader)?).ok()?;
+        let low_runs = read_track(&mut reader, runs.checked_mul(2)?)?
+            .chunks_exact(2)
+            .map(|run| (run[0], run[1]))
+            .collect();
+        // anything left over means the file is not what this version writes
+        if reader.read(&mut [0u8]).ok()? != 0 {
+            return None;
+        }
+        Some(RegionDepths {
+            depths: depths
+                .into_iter()
+                .enumerate()
+                .map(|(i, depth)| {
+                    let mut pos = BedGraph::new(String::from(tid), start + i as u32 * bin_size);
+                    pos.depth = depth;
+                    pos
+                })
+                .collect(),
+            reads,
+            bin_size,
+            histogram: DepthHistogram::from_counts(histogram),
+            low_runs,
+            reverse,
+            mapq,
+            softclips,
+            hints,
+            usable,
+            weighted,
+        })
+    }
+
+    // write through a temporary file, so a crash never leaves a truncated cache behind
+    fn store(&self, path: &Path, counted: &RegionDepths) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| anyhow!("failed to create {}: {}", self.dir.display(), e))?;
+        let tracks = [
+            (REVERSE_TRACK, &counted.reverse),
+            (MAPQ_TRACK, &counted.mapq),
+            (SOFTCLIP_TRACK, &counted.softclips),
+            (HINT_TRACK, &counted.hints),
+        ];
+        let mut mask = tracks
+            .iter()
+            .filter(|(_, track)| track.is_some())
+            .fold(0, |mask, (bit, _)| mask | bit);
+        // the arrays in the order of the mask bits, the usable track has one per step and the
+        // weighted one holds the bits of its floats
+        let mut arrays: Vec<&[u32]> = tracks
+            .iter()
+            .filter_map(|(_, track)| track.as_deref())
+            .collect();
+        if let Some(usable) = &counted.usable {
+            mask |= USABLE_TRACK;
+            arrays.extend(usable.iter().map(Vec::as_slice));
+        }
+        let weighted: Option<Vec<u32>> = counted
+            .weighted
+            .as_ref()
+            .map(|weighted| weighted.iter().map(|depth| depth.to_bits()).collect());
+        if let Some(weighted) = &weighted {
+            mask |= WEIGHTED_TRACK;
+            arrays.push(weighted);
+        }
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(counted.depths.len() as u64).to_le_bytes())?;
+        writer.write_all(&u64::from(counted.bin_size).to_le_bytes())?;
+        writer.write_all(&counted.reads.to_le_bytes())?;
+        writer.write_all(&mask.to_le_bytes())?;
+        for pos in &counted.depths {
+            writer.write_all(&pos.depth.to_le_bytes())?;
+        }
+        for values in arrays {
+            for value in values {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        let histogram = counted.histogram.counts();
+        writer.write_all(&(histogram.len() as u64).to_le_bytes())?;
+        for count in histogram {
+            writer.write_all(&count.to_le_bytes())?;
+        }
+        writer.write_all(&(counted.low_runs.len() as u64).to_le_bytes())?;
+        for (run_start, run_end) in &counted.low_runs {
+            writer.write_all(&run_start.to_le_bytes())?;
+            writer.write_all(&run_end.to_le_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Bits of the tracks a cache file holds, in the order they are written
+const REVERSE_TRACK: u64 = 1;
+const MAPQ_TRACK: u64 = 1 << 1;
+const SOFTCLIP_TRACK: u64 = 1 << 2;
+const HINT_TRACK: u64 = 1 << 3;
+const USABLE_TRACK: u64 = 1 << 4;
+const WEIGHTED_TRACK: u64 = 1 << 5;
+
+// 64-bit FNV-1a hash of a cache key
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// one little-endian u64 of a cache file
+fn read_u64(reader: &mut impl Read) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+// `len` little-endian u32 values of a cache file
+fn read_track(reader: &mut impl Read, len: usize) -> Option<Vec<u32>> {
+    let mut bytes = vec![0u8; len.checked_mul(4)?];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+impl RegionProcessor for CachedProcessor {
+    fn open_reader(&self) -> Result<bam::IndexedReader> {
+        self.inner.open_reader()
+    }
+
+    /// Load the region from the cache, or compute and save it
+    fn process_binned(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        binning: Binning,
+    ) -> Result<RegionDepths> {
+        // remote inputs have no modification time to check, so they are never cached
+        let Ok(path) = self.path(tid, start, stop, binning) else {
+            return self.inner.process_binned(tid, start, stop, binning);
+        };
+        let bins = (stop - start).div_ceil(binning.size.max(1)) as usize;
+        if let Some(counted) = Self::load(&path, tid, start).filter(|c| c.depths.len() == bins) {
+            log::debug!("{}:{}-{}: loaded from {}", tid, start, stop, path.display());
+            return Ok(counted);
+        }
+        let counted = self.inner.process_binned(tid, start, stop, binning)?;
+        // a cache that can't be written only costs speed
+        if let Err(e) = self.store(&path, &counted) {
+            log::warn!("failed to cache {}:{}-{}: {}", tid, start, stop, e);
+        }
+        Ok(counted)
+    }
+
+    /// Streamed output holds every position, so it is swept by the inner processor and not cached
+    fn stream_region(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>,
+    ) -> Result<()> {
+        self.inner.stream_region(tid, start, stop, emit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
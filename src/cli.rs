@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::{command, Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "tcov")]
@@ -10,20 +10,186 @@ use clap::{command, Parser, ValueEnum};
     help_template = "{name} -- {about}\n\nVersion: {version}\n\nAuthors: {author}\
     \n\n{usage-heading} {usage}\n\n{all-args}"
 )]
+#[command(subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// Input bam file with index
-    #[arg(short, long, help_heading = Some("Input Options"))]
-    pub bam: String,
-    /// input region, format: chr:start-end
-    #[arg(short, long, help_heading = Some("Input Options"))]
-    pub region: String,
-
-    /// Display color for coverage
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Input bam file with index, or an http(s):// or s3:// URL to one; repeat to stack several files for comparison
+    #[arg(short, long, required = true, help_heading = Some("Input Options"))]
+    pub bam: Vec<String>,
+    /// input region, format: chr:start-end, chr or gene:NAME with --annotation; repeat to view several regions, `-` reads one per line from stdin
+    #[arg(short, long, required_unless_present = "bed", help_heading = Some("Input Options"))]
+    pub region: Vec<String>,
+    /// How --region coordinates and the positions shown in the view count: 1 for 1-based inclusive like samtools and VCF (chr:1-100 is the first 100 bases), 0 for 0-based half-open like BED (chr:0-100); BED files are always 0-based
+    #[arg(default_value = "1", long, value_parser = clap::value_parser!(u32).range(0..=1), help_heading = Some("Input Options"))]
+    pub coord_base: u32,
+    /// Look up a contig missing from the header with the `chr` prefix added or removed, e.g. chr2 as 2, warning about it
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub normalize_contig: bool,
+    /// Bases added on each side of every region, cut at the contig ends
+    #[arg(default_value = "0", long, help_heading = Some("Input Options"))]
+    pub pad: u32,
+    /// BED file of regions to view, the optional name column is used as legend
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub bed: Option<String>,
+    /// Reference fasta file, required for cram input and --gc
+    #[arg(short = 'f', long, help_heading = Some("Input Options"))]
+    pub reference: Option<String>,
+    /// Indexed VCF/BCF whose variants are marked under the tracks, toggle with 'v' in the view
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub vcf: Option<String>,
+    /// BED or GFF/GTF of features such as genes and exons to draw under the tracks
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub annotation: Option<String>,
+    /// bedGraph of expected depths, such as a panel of normals, drawn as the log2 ratio of each track to it
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub baseline: Option<String>,
+    /// Index the input files that have none, as CSI when a contig is over 512Mb
+    #[arg(long, help_heading = Some("Input Options"))]
+    pub build_index: bool,
+
+    /// Display color for coverage: a name such as blue or lightred, #RRGGBB or r,g,b
     #[arg(default_value = "blue", short, long, help_heading = Some("Display Options"))]
     pub color: Color,
+    /// Color each column by its depth relative to the shared max instead of a flat color
+    #[arg(default_value = "none", long, help_heading = Some("Display Options"))]
+    pub colormap: Colormap,
+    /// How depth tracks are drawn: blocky bars, or a finer line of braille dots
+    #[arg(default_value = "sparkline", long, help_heading = Some("Display Options"))]
+    pub chart: Chart,
+    /// Display color for the reverse strand with `--strand both`, given like --color
+    #[arg(default_value = "red", long, help_heading = Some("Display Options"))]
+    pub reverse_color: Color,
+    /// Color of the coordinate labels, axes and help text, e.g. black on a light terminal, given like --color
+    #[arg(default_value = "cyan", long, help_heading = Some("Display Options"))]
+    pub label_color: Color,
+    /// Scale bar heights by log2(depth + 1), toggle with 'L' in the view
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub log: bool,
+    /// Show depth statistics of the region below the track, toggle with 's' in the view
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub stats: bool,
+    /// Draw columns below this depth in red, list the runs below it in the stats panel and jump between them with [ and ], raise or lower it with ▲ and ▼ in the view
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub min_cov: Option<u32>,
+    /// Clip bars above this depth so moderate coverage shows beside spikes, lower or raise it with { and } in the view
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help_heading = Some("Display Options"))]
+    pub max_depth: Option<u32>,
+    /// Bars of the depth histogram shown with 'd' in the view, as many as fit by default
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help_heading = Some("Display Options"))]
+    pub hist_bins: Option<u32>,
+    /// Depth a position needs to count toward the breadth shown in the title
+    #[arg(default_value = "1", long, help_heading = Some("Display Options"))]
+    pub min_breadth_depth: u32,
+    /// Draw the regions right to left, to read minus-strand genes 5' to 3'
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub flip: bool,
     /// Step size for moving the view
     #[arg(default_value = "10", short, long, help_heading = Some("Display Options"))]
     pub step_size: u8,
+    /// Show the GC percentage of the reference under the tracks, needs --reference
+    #[arg(long, requires = "reference", help_heading = Some("Display Options"))]
+    pub gc: bool,
+    /// Draw the columns where the reference is N in gray, coverage there is unreliable, needs --reference
+    #[arg(long, requires = "reference", help_heading = Some("Display Options"))]
+    pub mask_n: bool,
+    /// Show the mean mapping quality of the reads over each position under each track
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub mapq_track: bool,
+    /// Shade the part of each track made of reads with at least this MAPQ, to preview a stricter
+    /// --min-mapq, step it with U and u in the view; one of 0, 1, 10, 20, 30, 40 and 60
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub usable_mapq: Option<u8>,
+    /// Show the soft-clipped read ends at each position under each track, to spot breakpoints
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub softclip_track: bool,
+    /// Mark positions where many reads carry an indel or a base other than the most common one,
+    /// needs --mode pileup
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub variant_hints: bool,
+    /// Resume each region where it was last viewed, keeping the positions in this JSON file
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub session: Option<String>,
+    /// Milliseconds between redraws of the view, lower feels snappier on fast terminals
+    #[arg(default_value = "250", long, value_parser = clap::value_parser!(u64).range(1..), help_heading = Some("Display Options"))]
+    pub tick_ms: u64,
+    /// Redraw the view only after a key, mouse or resize event instead of every --tick-ms, to save CPU when left open
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub redraw_on_event: bool,
+    /// Leave the mouse to the terminal so text can be selected, instead of scrolling and dragging the tracks
+    #[arg(long, help_heading = Some("Display Options"))]
+    pub no_mouse: bool,
+    /// Bases merged into one data point in the view [default: auto for very large regions]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help_heading = Some("Display Options"))]
+    pub bin_size: Option<u32>,
+    /// How depths are merged into a bin, also used when zooming out
+    #[arg(default_value = "mean", long, help_heading = Some("Display Options"))]
+    pub bin_stat: BinStat,
+    /// Draw the centered moving average of this many data points, widen or narrow it with W and w in the view
+    #[arg(default_value = "1", long, value_parser = clap::value_parser!(u32).range(1..), help_heading = Some("Display Options"))]
+    pub smooth: u32,
+
+    /// Write depths to this file instead of launching the tui, `-` for stdout
+    #[arg(short, long, help_heading = Some("Output Options"))]
+    pub output: Option<String>,
+    /// Write depths to stdout instead of launching the tui
+    #[arg(long, help_heading = Some("Output Options"))]
+    pub no_tui: bool,
+    /// Print the resolved regions and the active filters, one key=value per line, and exit without counting
+    #[arg(long, help_heading = Some("Output Options"))]
+    pub dry_run: bool,
+    /// Format of the written depths
+    #[arg(default_value = "bedgraph", long, help_heading = Some("Output Options"))]
+    pub format: Format,
+    /// Include the depth array in `--format json`, binned by --bin-size and --bin-stat
+    #[arg(long, help_heading = Some("Output Options"))]
+    pub json_depths: bool,
+    /// Plot the whole region to this PNG instead of launching the tui, needs a single region
+    #[arg(long, help_heading = Some("Output Options"))]
+    pub screenshot: Option<String>,
+    /// Plot every region to its own PNG in this directory instead of launching the tui, named by the BED name or chrom_start-end
+    #[arg(long, conflicts_with = "screenshot", help_heading = Some("Output Options"))]
+    pub screenshot_dir: Option<String>,
+    /// Width of the screenshot images in pixels
+    #[arg(default_value = "1200", long, help_heading = Some("Output Options"))]
+    pub plot_width: u32,
+    /// Height of the screenshot images in pixels
+    #[arg(default_value = "400", long, help_heading = Some("Output Options"))]
+    pub plot_height: u32,
+    /// Exit with an error unless this fraction of the written regions is covered this deep, given as depth:fraction such as 30:0.95
+    #[arg(long, help_heading = Some("Output Options"))]
+    pub fail_under: Option<FailUnder>,
+    /// Log read counts and timings of each region to stderr, -vv to also log each fetch
+    #[arg(short, long, action = clap::ArgAction::Count, help_heading = Some("Output Options"))]
+    pub verbose: u8,
+    /// Only print errors to stderr, not the progress or the files saved or indexed
+    #[arg(short, long, conflicts_with = "verbose", help_heading = Some("Output Options"))]
+    pub quiet: bool,
+
+    /// Save computed depths in this directory and reuse them until the input file changes
+    #[arg(long, help_heading = Some("Depth Options"))]
+    pub cache_dir: Option<String>,
+    /// Depth computing mode, pileup is slower but matches `samtools depth -a`
+    #[arg(default_value = "fast", long, help_heading = Some("Depth Options"))]
+    pub mode: Mode,
+    /// What one unit of depth is, fragment counts mates and supplementary parts of a read once, fast mode only
+    #[arg(default_value = "read", long, help_heading = Some("Depth Options"))]
+    pub depth_unit: DepthUnit,
+    /// What each counted read adds to the depth, mapq weighs it MAPQ/60 capped at 1 and rounds the summed depth to whole reads, the view also shows it unrounded, fast mode only
+    #[arg(default_value = "flat", long, help_heading = Some("Depth Options"))]
+    pub weight: Weight,
+    /// Only count one strand, or show both as separate tracks
+    #[arg(long, help_heading = Some("Depth Options"))]
+    pub strand: Option<Strand>,
+    /// Number of threads to process regions with
+    #[arg(default_value = "1", short, long, help_heading = Some("Depth Options"))]
+    pub threads: usize,
+    /// Scale depths in the view by the mapped reads in the index, to compare libraries
+    #[arg(default_value = "none", long, help_heading = Some("Depth Options"))]
+    pub normalize: Normalize,
+    /// Leave deleted bases uncovered like introns in fast mode, as pileup mode always does
+    #[arg(long, help_heading = Some("Depth Options"))]
+    pub del_as_gap: bool,
 
     /// Included flags
     #[arg(default_value = "0", short, long, help_heading = Some("Filter Options"))]
@@ -31,9 +197,54 @@ pub struct Cli {
     /// Excluded flags
     #[arg(default_value = "0", short, long, help_heading = Some("Filter Options"))]
     pub exclude_flags: u16,
-    /// Minimum mapping quality
+    /// Minimum mapping quality, switch it off and on with M in the view
     #[arg(default_value = "0", short, long, help_heading = Some("Filter Options"))]
     pub min_mapq: u8,
+    /// Minimum base quality, only applies to pileup mode
+    #[arg(default_value = "0", long, help_heading = Some("Filter Options"))]
+    pub min_baseq: u8,
+    /// Minimum absolute insert size of paired reads
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub min_insert: Option<u32>,
+    /// Maximum absolute insert size of paired reads
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub max_insert: Option<u32>,
+    /// Only count paired reads mapped in a proper pair (0x2), single-end reads are unaffected, switch with P in the view
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub proper_pairs_only: bool,
+    /// Maximum edit distance to the reference (NM tag)
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub max_nm: Option<u32>,
+    /// Drop reads without an NM tag under --max-nm, instead of counting them
+    #[arg(long, requires = "max_nm", help_heading = Some("Filter Options"))]
+    pub drop_missing_nm: bool,
+    /// Minimum read length
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub min_read_len: Option<u32>,
+    /// Maximum read length
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub max_read_len: Option<u32>,
+    /// Count duplicate reads (0x400), left out by default like `samtools depth`, switch with D in the view
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub incl_dups: bool,
+    /// Count supplementary alignments (0x800) as extra depth, by default a split read counts once
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub count_supplementary: bool,
+    /// Only count reads of this read group (RG tag); repeat to keep several
+    #[arg(long, help_heading = Some("Filter Options"))]
+    pub read_group: Vec<String>,
+    /// Stack one track per sample of the read groups in each input file, instead of one per file
+    #[arg(long, conflicts_with = "read_group", help_heading = Some("Filter Options"))]
+    pub split_by_rg: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the depth at one position of each input file and exit, with the options given before it, e.g. `tcov -b a.bam -m 20 query chr1:12345`
+    Query {
+        /// Position as chr:pos, counted from --coord-base
+        position: String,
+    },
 }
 
 pub fn parse_cli() -> Result<Cli> {
@@ -41,7 +252,154 @@ pub fn parse_cli() -> Result<Cli> {
     Ok(cli)
 }
 
-#[derive(ValueEnum, Debug, Clone)]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Mode {
+    /// count aligned blocks, deletions included
+    fast,
+    /// walk the pileup, deletions excluded
+    pileup,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum DepthUnit {
+    /// each alignment, overlapping mates counted once
+    read,
+    /// each read name, however many alignments it has at a position
+    fragment,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum Weight {
+    /// one for each read
+    flat,
+    /// the confidence in the placement of the read, from its MAPQ
+    mapq,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Format {
+    /// ranges of equal depth merged into one line
+    bedgraph,
+    /// one line per position, like `samtools depth -a`
+    tsv,
+    /// summary statistics of each region as a JSON array
+    json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum Normalize {
+    /// raw depth
+    none,
+    /// depth per million mapped reads
+    cpm,
+}
+
+impl Normalize {
+    /// Factor turning a depth into the normalized unit, given the mapped reads of the file
+    pub fn factor(self, mapped_reads: u64) -> f64 {
+        let mapped = mapped_reads.max(1) as f64;
+        match self {
+            Normalize::none => 1.0,
+            Normalize::cpm => 1e6 / mapped,
+        }
+    }
+
+    /// Unit shown beside normalized values
+    pub fn unit(self) -> &'static str {
+        match self {
+            Normalize::none => "x",
+            Normalize::cpm => "CPM",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Colormap {
+    /// one flat color per track
+    none,
+    /// dark purple through green to yellow
+    viridis,
+    /// black through red to pale yellow
+    magma,
+    /// dark gray to white
+    grayscale,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Chart {
+    /// one bar per column, in eighths of a cell
+    sparkline,
+    /// a line of braille dots, four rows of dots per cell, without the --usable-mapq shading
+    braille,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum BinStat {
+    /// mean depth of the bin
+    mean,
+    /// highest depth of the bin
+    max,
+    /// lowest depth of the bin
+    min,
+}
+
+impl From<BinStat> for tcov::cov::BinStat {
+    fn from(stat: BinStat) -> Self {
+        match stat {
+            BinStat::mean => Self::Mean,
+            BinStat::max => Self::Max,
+            BinStat::min => Self::Min,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Strand {
+    /// forward above, reverse below
+    both,
+    /// forward strand reads only
+    forward,
+    /// reverse strand reads only
+    reverse,
+}
+
+/// Breadth the written regions must reach, from `--fail-under depth:fraction`
+#[derive(Debug, Clone, Copy)]
+pub struct FailUnder {
+    pub depth: u32,
+    /// fraction of the bases that must be covered at least `depth` deep
+    pub fraction: f64,
+}
+
+impl std::str::FromStr for FailUnder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid `{}`, expected depth:fraction such as 30:0.95", s);
+        let (depth, fraction) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let depth = depth.trim().parse().map_err(|_| invalid())?;
+        let fraction: f64 = fraction.trim().parse().map_err(|_| invalid())?;
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(format!(
+                "fraction {} of `{}` is not between 0 and 1",
+                fraction, s
+            ));
+        }
+        Ok(FailUnder { depth, fraction })
+    }
+}
+
+/// A terminal color given by name, `#RRGGBB` or `r,g,b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum Color {
     black,
@@ -60,29 +418,108 @@ pub enum Color {
     lightmagenta,
     lightcyan,
     white,
+    /// any 24-bit color, for terminals that support it
+    rgb(u8, u8, u8),
+}
+
+/// Named colors as typed on the command line
+const COLOR_NAMES: [(&str, Color); 16] = [
+    ("black", Color::black),
+    ("red", Color::red),
+    ("green", Color::green),
+    ("yellow", Color::yellow),
+    ("blue", Color::blue),
+    ("magenta", Color::magenta),
+    ("cyan", Color::cyan),
+    ("gray", Color::gray),
+    ("darkgray", Color::darkgray),
+    ("lightred", Color::lightred),
+    ("lightgreen", Color::lightgreen),
+    ("lightyellow", Color::lightyellow),
+    ("lightblue", Color::lightblue),
+    ("lightmagenta", Color::lightmagenta),
+    ("lightcyan", Color::lightcyan),
+    ("white", Color::white),
+];
+
+impl Color {
+    /// The same color for drawing in the terminal
+    pub fn to_ratatui_color(self) -> ratatui::style::Color {
+        use ratatui::style::Color as C;
+        match self {
+            Color::black => C::Black,
+            Color::red => C::Red,
+            Color::green => C::Green,
+            Color::yellow => C::Yellow,
+            Color::blue => C::Blue,
+            Color::magenta => C::Magenta,
+            Color::cyan => C::Cyan,
+            Color::gray => C::Gray,
+            Color::darkgray => C::DarkGray,
+            Color::lightred => C::LightRed,
+            Color::lightgreen => C::LightGreen,
+            Color::lightyellow => C::LightYellow,
+            Color::lightblue => C::LightBlue,
+            Color::lightmagenta => C::LightMagenta,
+            Color::lightcyan => C::LightCyan,
+            Color::white => C::White,
+            Color::rgb(r, g, b) => C::Rgb(r, g, b),
+        }
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color::rgb(r, g, b)),
+                _ => Err(format!("invalid hex color `{}`, expected #RRGGBB", s)),
+            };
+        }
+        if s.contains(',') {
+            let channels: Vec<Option<u8>> = s.split(',').map(|c| c.trim().parse().ok()).collect();
+            return match channels[..] {
+                [Some(r), Some(g), Some(b)] => Ok(Color::rgb(r, g, b)),
+                _ => Err(format!(
+                    "invalid color `{}`, expected r,g,b with values from 0 to 255",
+                    s
+                )),
+            };
+        }
+        let lower = s.to_ascii_lowercase();
+        COLOR_NAMES
+            .iter()
+            .find(|(name, _)| *name == lower)
+            .map(|(_, color)| *color)
+            .ok_or_else(|| {
+                let names: Vec<&str> = COLOR_NAMES.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "unknown color `{}`, expected #RRGGBB, r,g,b or one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
 }
 
-// impl to_string for Color
-// what a dummy impl HAHAHA
 impl std::fmt::Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Color::black => write!(f, "black"),
-            Color::red => write!(f, "red"),
-            Color::green => write!(f, "green"),
-            Color::yellow => write!(f, "yellow"),
-            Color::blue => write!(f, "blue"),
-            Color::magenta => write!(f, "magenta"),
-            Color::cyan => write!(f, "cyan"),
-            Color::gray => write!(f, "gray"),
-            Color::darkgray => write!(f, "darkgray"),
-            Color::lightred => write!(f, "lightred"),
-            Color::lightgreen => write!(f, "lightgreen"),
-            Color::lightyellow => write!(f, "lightyellow"),
-            Color::lightblue => write!(f, "lightblue"),
-            Color::lightmagenta => write!(f, "lightmagenta"),
-            Color::lightcyan => write!(f, "lightcyan"),
-            Color::white => write!(f, "white"),
+            Color::rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            named => {
+                let name = COLOR_NAMES
+                    .iter()
+                    .find(|(_, color)| color == named)
+                    .map_or("?", |(name, _)| name);
+                write!(f, "{}", name)
+            }
         }
     }
 }
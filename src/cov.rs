@@ -1,12 +1,21 @@
 //! This feature is inspired by [perbase](https://github.com/sstadick/perbase)
-use anyhow::Result;
-use rust_htslib::bam::pileup::Alignment;
-use rust_htslib::bam::record::Record;
+use crate::progress::RegionProgress;
+use crate::stats::DepthHistogram;
+use anyhow::{anyhow, Context, Result};
+use indicatif::ProgressBar;
+use rust_htslib::bam::pileup::{Alignment, Indel};
+use rust_htslib::bam::record::{Aux, Record};
 use rust_htslib::{bam, bam::ext::BamRecordExtensions, bam::record::Cigar, bam::Read};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use std::fmt;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use std::{convert::TryFrom, rc::Rc};
 use std::{default, fmt::Display};
+use url::Url;
 
 /// A serializable object meant to hold all information about a position.
 pub trait Position: Default {
@@ -41,10 +50,385 @@ impl Position for BedGraph {
     }
 }
 
+/// Depths of a region with the number of reads behind them, and the [Tracks] counted with them.
+///
+/// Every array holds one value per bin of [RegionDepths::bin_size] positions, the last bin may
+/// be shorter; bins of one position are the positions themselves.
+pub struct RegionDepths {
+    /// depth of each bin at its first position, of the forward strand only with
+    /// [Tracks::reverse]
+    pub depths: Vec<BedGraph>,
+    /// reads that passed the filters and were counted
+    pub reads: u64,
+    /// positions merged into each bin
+    pub bin_size: u32,
+    /// depths of both strands of every position, before binning
+    pub histogram: DepthHistogram,
+    /// runs of positions below [Binning::low_depth], both strands together, as 0-based
+    /// half-open ranges
+    pub low_runs: Vec<(u32, u32)>,
+    /// depth of the reverse strand, with [Tracks::reverse]
+    pub reverse: Option<Vec<u32>>,
+    /// mean MAPQ of the reads counted, 0 where there are none, with [Tracks::mapq]
+    pub mapq: Option<Vec<u32>>,
+    /// soft-clipped read ends, the most of any position of a bin, with [Tracks::softclips]
+    pub softclips: Option<Vec<u32>>,
+    /// percentage of the reads disagreeing, the most of any position of a bin, with
+    /// [Tracks::hints]
+    pub hints: Option<Vec<u32>>,
+    /// depth of the reads reaching each MAPQ of [MAPQ_STEPS], one array per step, with
+    /// [Tracks::usable]
+    pub usable: Option<Vec<Vec<u32>>>,
+    /// depth of both strands together before it is rounded to whole reads, with
+    /// [DepthProcessor::weight_by_mapq]
+    pub weighted: Option<Vec<f32>>,
+}
+
+/// What is counted alongside the depth, in the same pass over the reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tracks {
+    /// count the reverse strand apart, the depth then only counts the forward one
+    pub reverse: bool,
+    /// the mean MAPQ of the reads at each position, both strands together
+    pub mapq: bool,
+    /// the soft-clipped read ends at each position, both strands together; a clip is counted
+    /// at the aligned base next to it, so piles of clips flag structural breakpoints
+    pub softclips: bool,
+    /// the percentage of the reads disagreeing at each position, only walking the pileup with
+    /// [PileupDepthProcessor]. A read disagrees when it has a deletion or an insertion there,
+    /// or a base other than the most common one of the column, so no reference is needed;
+    /// positions covered by fewer than [MIN_HINT_DEPTH] reads are 0.
+    pub hints: bool,
+    /// the depth counting only the reads with at least each MAPQ of [MAPQ_STEPS], only summing
+    /// blocks with [DepthProcessor]. The reads are those of the depth, the forward strand ones
+    /// with [Tracks::reverse], so the first step is the depth itself.
+    pub usable: bool,
+}
+
+/// Count the soft clips of a read at the aligned base next to each, the first one for a
+/// leading clip and the last one for a trailing clip, if it is within `start` to `stop`.
+///
+/// Clips are few, so they are kept by position rather than in one slot per position.
+fn count_softclips(clips: &mut BTreeMap<u32, u32>, start: u32, stop: u32, read: &Record) {
+    let cigar = read.cigar();
+    let mut count = |pos: i64| {
+        if let Ok(pos) = u32::try_from(pos) {
+            if (start..stop).contains(&pos) {
+                *clips.entry(pos).or_default() += 1;
+            }
+        }
+    };
+    if cigar.leading_softclips() > 0 {
+        count(read.reference_start());
+    }
+    if cigar.trailing_softclips() > 0 {
+        count(read.reference_end() - 1);
+    }
+}
+
+/// How the values of the positions of a bin are merged into one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinStat {
+    /// mean value of the bin
+    #[default]
+    Mean,
+    /// highest value of the bin
+    Max,
+    /// lowest value of the bin
+    Min,
+}
+
+/// Bins the depths of a region are merged into as they are summed, so a large region never
+/// holds one depth per position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binning {
+    /// positions merged into each bin
+    pub size: u32,
+    /// how the depths of a bin are merged; the MAPQ is always averaged, and the soft clips and
+    /// hints take the most of any position
+    pub stat: BinStat,
+    /// keep the runs of positions below this depth in [RegionDepths::low_runs]
+    pub low_depth: Option<u32>,
+}
+
+impl Default for Binning {
+    /// One position per bin
+    fn default() -> Self {
+        Self {
+            size: 1,
+            stat: BinStat::Mean,
+            low_depth: None,
+        }
+    }
+}
+
+/// Values of consecutive positions merged into bins as they are pushed
+struct Bins {
+    size: u32,
+    stat: BinStat,
+    values: Vec<f64>,
+    /// merged value of the bin being filled, and its positions so far
+    merged: f64,
+    filled: u32,
+}
+
+impl Bins {
+    fn new(binning: Binning, stat: BinStat, len: usize) -> Self {
+        let size = binning.size.max(1);
+        Self {
+            size,
+            stat,
+            values: Vec::with_capacity(len.div_ceil(size as usize)),
+            merged: 0.0,
+            filled: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.merged = match (self.stat, self.filled) {
+            (_, 0) => value,
+            (BinStat::Mean, _) => self.merged + value,
+            (BinStat::Max, _) => self.merged.max(value),
+            (BinStat::Min, _) => self.merged.min(value),
+        };
+        self.filled += 1;
+        if self.filled == self.size {
+            self.close();
+        }
+    }
+
+    fn close(&mut self) {
+        self.values.push(match self.stat {
+            BinStat::Mean => self.merged / f64::from(self.filled),
+            BinStat::Max | BinStat::Min => self.merged,
+        });
+        self.filled = 0;
+    }
+
+    fn finish(mut self) -> Vec<f64> {
+        if self.filled > 0 {
+            self.close();
+        }
+        self.values
+    }
+
+    // whole values, a mean is rounded down
+    fn finish_whole(self) -> Vec<u32> {
+        self.finish()
+            .into_iter()
+            .map(|value| value as u32)
+            .collect()
+    }
+}
+
+/// Values of one position, pushed to [BinnedTracks]; the tracks not counted stay 0
+#[derive(Default)]
+struct Column {
+    depth: u32,
+    reverse: u32,
+    mapq: u32,
+    hint: u32,
+    /// depth of the reads reaching each of [MAPQ_STEPS]
+    usable: [u32; MAPQ_STEPS.len()],
+    weighted: f64,
+}
+
+/// Depths and tracks of a region merged into bins as its positions are pushed in order, with
+/// the histogram and low runs of the depth of both strands
+struct BinnedTracks {
+    binning: Binning,
+    start: u32,
+    /// next position to push
+    pos: u32,
+    depths: Bins,
+    reverse: Option<Bins>,
+    mapq: Option<Bins>,
+    hints: Option<Bins>,
+    usable: Option<Vec<Bins>>,
+    weighted: Option<Bins>,
+    histogram: DepthHistogram,
+    low_runs: Vec<(u32, u32)>,
+}
+
+impl BinnedTracks {
+    fn new(tracks: Tracks, weighted: bool, binning: Binning, start: u32, stop: u32) -> Self {
+        let len = (stop - start) as usize;
+        let bins = |stat| Bins::new(binning, stat, len);
+        Self {
+            binning,
+            start,
+            pos: start,
+            depths: bins(binning.stat),
+            reverse: tracks.reverse.then(|| bins(binning.stat)),
+            mapq: tracks.mapq.then(|| bins(BinStat::Mean)),
+            hints: tracks.hints.then(|| bins(BinStat::Max)),
+            usable: tracks
+                .usable
+                .then(|| MAPQ_STEPS.iter().map(|_| bins(binning.stat)).collect()),
+            weighted: weighted.then(|| bins(binning.stat)),
+            histogram: DepthHistogram::default(),
+            low_runs: Vec::new(),
+        }
+    }
+
+    // push the values of the next position
+    fn push(&mut self, column: &Column) {
+        self.depths.push(f64::from(column.depth));
+        if let Some(reverse) = self.reverse.as_mut() {
+            reverse.push(f64::from(column.reverse));
+        }
+        if let Some(mapq) = self.mapq.as_mut() {
+            mapq.push(f64::from(column.mapq));
+        }
+        if let Some(hints) = self.hints.as_mut() {
+            hints.push(f64::from(column.hint));
+        }
+        for (steps, &depth) in self.usable.iter_mut().flatten().zip(&column.usable) {
+            steps.push(f64::from(depth));
+        }
+        if let Some(weighted) = self.weighted.as_mut() {
+            weighted.push(column.weighted);
+        }
+        let depth = column.depth + column.reverse;
+        self.histogram.add(depth);
+        if self.binning.low_depth.is_some_and(|low| depth < low) {
+            match self.low_runs.last_mut() {
+                Some((_, end)) if *end == self.pos => *end += 1,
+                _ => self.low_runs.push((self.pos, self.pos + 1)),
+            }
+        }
+        self.pos += 1;
+    }
+
+    // push empty positions up to `pos`, for the positions no read covers
+    fn fill_to(&mut self, pos: u32) {
+        while self.pos < pos {
+            self.push(&Column::default());
+        }
+    }
+
+    fn finish(self, tid: &str, reads: u64, softclips: Option<BTreeMap<u32, u32>>) -> RegionDepths {
+        let size = self.binning.size.max(1);
+        let depths = self.depths.finish_whole();
+        // the busiest position of a bin marks a breakpoint best
+        let softclips = softclips.map(|clips| {
+            let mut binned = vec![0; depths.len()];
+            for (pos, count) in clips {
+                let bin = &mut binned[((pos - self.start) / size) as usize];
+                *bin = (*bin).max(count);
+            }
+            binned
+        });
+        RegionDepths {
+            depths: depths
+                .into_iter()
+                .enumerate()
+                .map(|(i, depth)| {
+                    let mut pos = BedGraph::new(String::from(tid), self.start + i as u32 * size);
+                    pos.depth = depth;
+                    pos
+                })
+                .collect(),
+            reads,
+            bin_size: size,
+            histogram: self.histogram,
+            low_runs: self.low_runs,
+            reverse: self.reverse.map(Bins::finish_whole),
+            mapq: self.mapq.map(Bins::finish_whole),
+            softclips,
+            hints: self.hints.map(Bins::finish_whole),
+            usable: self
+                .usable
+                .map(|steps| steps.into_iter().map(Bins::finish_whole).collect()),
+            weighted: self.weighted.map(|weighted| {
+                weighted
+                    .finish()
+                    .into_iter()
+                    .map(|depth| depth as f32)
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// Hold the depth of a half-open range of positions, as written on one bedGraph line.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RangePosition {
+    /// Reference sequence name.
+    pub ref_seq: String,
+    /// 0-based start of the range.
+    pub start: u32,
+    /// Exclusive end of the range.
+    pub end: u32,
+    /// Depth at every position of the range.
+    pub depth: u32,
+}
+
+impl Display for RangePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.ref_seq, self.start, self.end, self.depth
+        )
+    }
+}
+
+impl Position for RangePosition {
+    /// Create a range of the single position, with depth zeroed.
+    fn new(ref_seq: String, pos: u32) -> Self {
+        RangePosition {
+            ref_seq,
+            start: pos,
+            end: pos + 1,
+            ..default::Default::default()
+        }
+    }
+}
+
+/// Merge consecutive positions of the same contig and depth into ranges, in order.
+pub fn merge_positions(positions: &[BedGraph]) -> Vec<RangePosition> {
+    let mut ranges: Vec<RangePosition> = Vec::new();
+    for pos in positions {
+        match ranges.last_mut() {
+            Some(last)
+                if last.end == pos.pos
+                    && last.depth == pos.depth
+                    && last.ref_seq == pos.ref_seq =>
+            {
+                last.end += 1
+            }
+            _ => {
+                let mut range = RangePosition::new(pos.ref_seq.clone(), pos.pos);
+                range.depth = pos.depth;
+                ranges.push(range);
+            }
+        }
+    }
+    ranges
+}
+
 /// Anything that implements ReadFilter can apply a filter set to read.
 pub trait ReadFilter {
     /// filters a read, true is pass, false if fail
     fn filter_read(&self, read: &Record, alignment: Option<&Alignment>) -> bool;
+
+    /// Names of the filters a read goes through, in order, to report [ReadFilter::rejected_by]
+    fn names(&self) -> Vec<String> {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        vec![name.rsplit("::").next().unwrap_or(name).to_string()]
+    }
+
+    /// Number of [ReadFilter::names], one unless the filter stacks several
+    fn filter_count(&self) -> usize {
+        1
+    }
+
+    /// Index in [ReadFilter::names] of the filter rejecting a read, `None` if it passes
+    fn rejected_by(&self, read: &Record, alignment: Option<&Alignment>) -> Option<usize> {
+        (!self.filter_read(read, alignment)).then_some(0)
+    }
 }
 
 /// A straightforward read filter.
@@ -55,7 +439,7 @@ pub struct DefaultReadFilter {
 }
 
 impl DefaultReadFilter {
-    /// Create an OnlyDepthReadFilter
+    /// Create a DefaultReadFilter
     pub fn new(include_flags: u16, exclude_flags: u16, min_mapq: u8) -> Self {
         Self {
             include_flags,
@@ -76,47 +460,210 @@ impl ReadFilter for DefaultReadFilter {
     }
 }
 
+/// Stack several filters, a read passes only if it passes all of them.
+///
+/// An empty AndFilter passes every read.
+#[derive(Default)]
+pub struct AndFilter(pub Vec<Box<dyn ReadFilter + Send + Sync>>);
+
+impl AndFilter {
+    /// Create an AndFilter without any filter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter to the stack
+    pub fn and(mut self, filter: impl ReadFilter + Send + Sync + 'static) -> Self {
+        self.0.push(Box::new(filter));
+        self
+    }
+}
+
+impl ReadFilter for AndFilter {
+    /// Filter reads with every filter in turn, stopping at the first failure
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, alignment: Option<&Alignment>) -> bool {
+        self.0
+            .iter()
+            .all(|filter| filter.filter_read(read, alignment))
+    }
+
+    /// Names of the stacked filters, those of a nested stack flattened in place
+    fn names(&self) -> Vec<String> {
+        self.0.iter().flat_map(|filter| filter.names()).collect()
+    }
+
+    fn filter_count(&self) -> usize {
+        self.0.iter().map(|filter| filter.filter_count()).sum()
+    }
+
+    /// Index of the first stacked filter rejecting the read, counted across nested stacks
+    fn rejected_by(&self, read: &Record, alignment: Option<&Alignment>) -> Option<usize> {
+        let mut offset = 0;
+        for filter in &self.0 {
+            if let Some(index) = filter.rejected_by(read, alignment) {
+                return Some(offset + index);
+            }
+            offset += filter.filter_count();
+        }
+        None
+    }
+}
+
+/// Reject pairs whose insert size is out of range, unpaired reads always pass.
+pub struct InsertSizeFilter {
+    min_insert: Option<u32>,
+    max_insert: Option<u32>,
+}
+
+impl InsertSizeFilter {
+    /// Create an InsertSizeFilter, a missing bound is not checked
+    pub fn new(min_insert: Option<u32>, max_insert: Option<u32>) -> Self {
+        Self {
+            min_insert,
+            max_insert,
+        }
+    }
+}
+
+impl ReadFilter for InsertSizeFilter {
+    /// Filter reads by the absolute template length
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, _alignment: Option<&Alignment>) -> bool {
+        if !read.is_paired() {
+            return true;
+        }
+        let insert = read.insert_size().unsigned_abs();
+        self.min_insert.is_none_or(|min| insert >= min as u64)
+            && self.max_insert.is_none_or(|max| insert <= max as u64)
+    }
+}
+
+/// Keep only paired reads mapped in a proper pair (0x2), unpaired reads always pass.
+///
+/// Single-end data has no pair flag (0x1), so it is left as it is.
+pub struct ProperPairFilter;
+
+impl ReadFilter for ProperPairFilter {
+    /// Filter paired reads by the proper pair flag
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, _alignment: Option<&Alignment>) -> bool {
+        !read.is_paired() || read.is_proper_pair()
+    }
+}
+
+/// Reject reads whose edit distance (`NM` tag) to the reference is above a maximum.
+pub struct EditDistanceFilter {
+    max_nm: u32,
+    /// let reads without an `NM` tag through
+    keep_missing: bool,
+}
+
+impl EditDistanceFilter {
+    /// Create an EditDistanceFilter, reads without the tag pass if `keep_missing` is set
+    pub fn new(max_nm: u32, keep_missing: bool) -> Self {
+        Self {
+            max_nm,
+            keep_missing,
+        }
+    }
+}
+
+impl ReadFilter for EditDistanceFilter {
+    /// Filter reads by the `NM` aux tag, whichever integer type it is stored as
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, _alignment: Option<&Alignment>) -> bool {
+        let nm = match read.aux(b"NM") {
+            Ok(Aux::U8(nm)) => i64::from(nm),
+            Ok(Aux::U16(nm)) => i64::from(nm),
+            Ok(Aux::U32(nm)) => i64::from(nm),
+            Ok(Aux::I8(nm)) => i64::from(nm),
+            Ok(Aux::I16(nm)) => i64::from(nm),
+            Ok(Aux::I32(nm)) => i64::from(nm),
+            _ => return self.keep_missing,
+        };
+        nm <= i64::from(self.max_nm)
+    }
+}
+
+/// Reject reads whose length is out of range.
+pub struct ReadLengthFilter {
+    min_len: Option<u32>,
+    max_len: Option<u32>,
+}
+
+impl ReadLengthFilter {
+    /// Create a ReadLengthFilter, a missing bound is not checked
+    pub fn new(min_len: Option<u32>, max_len: Option<u32>) -> Self {
+        Self { min_len, max_len }
+    }
+}
+
+impl ReadFilter for ReadLengthFilter {
+    /// Filter reads by the length of the stored sequence
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, _alignment: Option<&Alignment>) -> bool {
+        let len = read.seq_len();
+        self.min_len.is_none_or(|min| len >= min as usize)
+            && self.max_len.is_none_or(|max| len <= max as usize)
+    }
+}
+
+/// Keep only reads whose `RG` tag is one of the given read groups, reads without it are dropped.
+pub struct ReadGroupFilter {
+    groups: Vec<String>,
+}
+
+impl ReadGroupFilter {
+    /// Create a ReadGroupFilter
+    pub fn new(groups: Vec<String>) -> Self {
+        Self { groups }
+    }
+}
+
+impl ReadFilter for ReadGroupFilter {
+    /// Filter reads by the `RG` aux tag
+    #[inline(always)]
+    fn filter_read(&self, read: &Record, _alignment: Option<&Alignment>) -> bool {
+        match read.aux(b"RG") {
+            Ok(Aux::String(rg)) => self.groups.iter().any(|group| group == rg),
+            _ => false,
+        }
+    }
+}
+
 // A tweaked impl of IterAlignedBlocks from [here](https://github.com/rust-bio/rust-htslib/blob/9175d3ca186baef4f84a7d7ccb27869b43471e36/src/bam/ext.rs#L51)
-// Not that this will also hang onto the bam::Record and supplies the qname for each thing returned.
-// At the end of the day this shouldn't be the worst since any given read should not have that many splits in it
+// yielding the 0-based half-open reference range of each aligned block of a read
 struct IterAlignedBlocks {
     pos: i64,
     cigar_index: usize,
     cigar: bam::record::CigarStringView,
-    overlap_status: bool,
-    record: Rc<bam::Record>,
+    /// skip deletions like introns instead of yielding them as covered
+    del_as_gap: bool,
 }
 impl IterAlignedBlocks {
-    fn new(record: Rc<bam::Record>) -> Self {
-        let overlap = false;
+    fn new(record: &Record, del_as_gap: bool) -> Self {
         Self {
             pos: record.reference_start(),
             cigar_index: 0,
             cigar: record.cigar(),
-            overlap_status: overlap,
-            record,
+            del_as_gap,
         }
     }
 }
 
 impl Iterator for IterAlignedBlocks {
-    type Item = (i64, i64, bool, String);
+    type Item = (i64, i64);
     fn next(&mut self) -> Option<Self::Item> {
         while self.cigar_index < self.cigar.len() {
             let entry = self.cigar[self.cigar_index];
             match entry {
+                Cigar::Del(len) if self.del_as_gap => self.pos += len as i64,
                 Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) | Cigar::Del(len) => {
                     let out_pos = self.pos;
                     self.pos += len as i64;
                     self.cigar_index += 1;
-                    return Some((
-                        out_pos,
-                        out_pos + len as i64,
-                        self.overlap_status,
-                        String::from(
-                            std::str::from_utf8(self.record.qname()).expect("Convert qname"),
-                        ),
-                    ));
+                    return Some((out_pos, out_pos + len as i64));
                 }
                 Cigar::RefSkip(len) => self.pos += len as i64,
                 _ => (),
@@ -127,89 +674,1341 @@ impl Iterator for IterAlignedBlocks {
     }
 }
 
-pub(crate) struct DepthProcessor<F: ReadFilter + Send> {
+/// Whether a read is a primary alignment whose mate is mapped, so the pair may overlap
+#[inline]
+fn is_mate_candidate(read: &Record) -> bool {
+    read.is_paired() && !read.is_mate_unmapped() && !read.is_secondary() && !read.is_supplementary()
+}
+
+/// Remove from `blocks` the parts already covered by the mate's sorted `covered` blocks
+fn subtract_blocks(blocks: Vec<(i64, i64)>, covered: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let mut result = Vec::with_capacity(blocks.len());
+    for (mut start, end) in blocks {
+        for &(covered_start, covered_end) in covered {
+            if covered_end <= start || covered_start >= end {
+                continue;
+            }
+            if covered_start > start {
+                result.push((start, covered_start));
+            }
+            start = start.max(covered_end);
+            if start >= end {
+                break;
+            }
+        }
+        if start < end {
+            result.push((start, end));
+        }
+    }
+    result
+}
+
+/// Blocks already counted for each fragment, so its other alignments only add what is new
+#[derive(Default)]
+struct FragmentBlocks {
+    /// sorted, non-overlapping blocks counted so far, keyed by qname
+    blocks: HashMap<Vec<u8>, Vec<(i64, i64)>>,
+    /// end of the blocks of a fragment each time they grew, earliest first
+    ends: BinaryHeap<Reverse<(i64, Vec<u8>)>>,
+}
+
+impl FragmentBlocks {
+    // the parts of `blocks` that no earlier alignment of the fragment covered
+    fn add(&mut self, qname: &[u8], blocks: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        if blocks.is_empty() {
+            return blocks;
+        }
+        let covered = self.blocks.entry(qname.to_vec()).or_default();
+        let new = subtract_blocks(blocks, covered);
+        if !new.is_empty() {
+            covered.extend(&new);
+            covered.sort_unstable();
+            let end = covered.last().map_or(0, |block| block.1);
+            self.ends.push(Reverse((end, qname.to_vec())));
+        }
+        new
+    }
+
+    // drop the fragments whose blocks all end by `pos`, as reads come sorted by start no
+    // later alignment can overlap them
+    fn forget_before(&mut self, pos: i64) {
+        while self
+            .ends
+            .peek()
+            .is_some_and(|Reverse((end, _))| *end <= pos)
+        {
+            let Some(Reverse((_, qname))) = self.ends.pop() else {
+                break;
+            };
+            let done = self
+                .blocks
+                .get(&qname)
+                .and_then(|blocks| blocks.last())
+                .is_some_and(|block| block.1 <= pos);
+            if done {
+                self.blocks.remove(&qname);
+            }
+        }
+    }
+}
+
+/// URL schemes an input is read from over the network, s3 needs the `s3` feature
+const REMOTE_SCHEMES: [&str; 4] = ["http", "https", "ftp", "s3"];
+
+/// The URL of a remote input, `None` for a local path
+pub fn remote_url(reads: &Path) -> Option<Url> {
+    let url = Url::parse(reads.to_str()?).ok()?;
+    REMOTE_SCHEMES.contains(&url.scheme()).then_some(url)
+}
+
+/// Check whether the input is a CRAM, by extension first and then by magic bytes
+fn is_cram(path: &Path) -> Result<bool> {
+    // reading the magic bytes of a URL would be another request, and it may carry a query
+    if let Some(url) = remote_url(path) {
+        return Ok(url.path().to_ascii_lowercase().ends_with(".cram"));
+    }
+    if let Some(ext) = path.extension() {
+        if ext.eq_ignore_ascii_case("cram") {
+            return Ok(true);
+        }
+    }
+    let mut magic = [0u8; 4];
+    let mut file = File::open(path)?;
+    Ok(file.read_exact(&mut magic).is_ok() && &magic == b"CRAM")
+}
+
+/// Longest contig a BAI index can address, longer ones need CSI
+const BAI_MAX_LEN: u64 = 1 << 29;
+
+/// Minimum shift of built CSI indexes, the `samtools index -c` default
+const CSI_MIN_SHIFT: u32 = 14;
+
+/// Index of the input next to it, looked up where htslib does: `x.bam.bai` or `x.bai`,
+/// the same with `.csi`, and `x.cram.crai`
+fn find_index(reads: &Path) -> Option<PathBuf> {
+    let name = reads.as_os_str();
+    ["bai", "csi", "crai"]
+        .into_iter()
+        .flat_map(|ext| {
+            let mut appended = name.to_os_string();
+            appended.push(".");
+            appended.push(ext);
+            [PathBuf::from(appended), reads.with_extension(ext)]
+        })
+        .find(|path| path.exists())
+}
+
+/// Build the index of a local input that has none, CSI if a contig is too long for BAI;
+/// return whether one was built
+pub fn build_missing_index(reads: &Path, threads: usize) -> Result<bool> {
+    // remote inputs are left to htslib
+    if !reads.exists() || find_index(reads).is_some() {
+        return Ok(false);
+    }
+    let reader = bam::Reader::from_path(reads)?;
+    let header = reader.header();
+    let longest = (0..header.target_count())
+        .filter_map(|tid| header.target_len(tid))
+        .max()
+        .unwrap_or(0);
+    // CRAM gets a .crai whatever the type
+    let idx_type = if longest > BAI_MAX_LEN {
+        bam::index::Type::Csi(CSI_MIN_SHIFT)
+    } else {
+        bam::index::Type::Bai
+    };
+    bam::index::build(reads, None, idx_type, u32::try_from(threads.max(1))?)
+        .map_err(|e| anyhow!("failed to index {}: {}", reads.display(), e))?;
+    Ok(true)
+}
+
+/// Open an indexed reader, setting the reference for CRAM input
+fn open_indexed_reader(reads: &Path, reference: Option<&Path>) -> Result<bam::IndexedReader> {
+    if reads.exists() && find_index(reads).is_none() {
+        return Err(anyhow!(
+            "{} has no .bai, .csi or .crai index, run `samtools index {}` \
+             (with -c if a contig is over 512Mb) or pass --build-index",
+            reads.display(),
+            reads.display()
+        ));
+    }
+    let mut reader = match remote_url(reads) {
+        // htslib looks for the index next to the URL
+        Some(url) => bam::IndexedReader::from_url(&url).map_err(|e| {
+            anyhow!(
+                "failed to open {} or its .bai, .csi or .crai index next to it: {}; \
+                 reading URLs needs htslib with libcurl, and s3:// the `s3` feature",
+                url,
+                e
+            )
+        })?,
+        None => bam::IndexedReader::from_path(reads)
+            .with_context(|| format!("failed to open {}", reads.display()))?,
+    };
+
+    // CRAM needs the reference to decode records
+    if is_cram(reads)? {
+        match reference {
+            Some(reference) => reader.set_reference(reference).with_context(|| {
+                format!(
+                    "failed to set the reference {} of {}",
+                    reference.display(),
+                    reads.display()
+                )
+            })?,
+            None => {
+                return Err(anyhow!(
+                    "{} is a CRAM file, please provide its reference with --reference",
+                    reads.display()
+                ))
+            }
+        }
+    }
+    Ok(reader)
+}
+
+/// Contig names listed in an error before the rest are only counted
+const MAX_LISTED_CONTIGS: usize = 20;
+
+/// Error for a contig missing from the header, listing the contigs that are there
+fn missing_contig(header: &bam::HeaderView, tid: &str) -> anyhow::Error {
+    let names: Vec<String> = header
+        .target_names()
+        .iter()
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect();
+    let mut listed = names
+        .iter()
+        .take(MAX_LISTED_CONTIGS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > MAX_LISTED_CONTIGS {
+        listed.push_str(&format!(" and {} more", names.len() - MAX_LISTED_CONTIGS));
+    }
+    if names.is_empty() {
+        listed = "none".to_string();
+    }
+    let other = toggle_chr_prefix(tid);
+    if header.tid(other.as_bytes()).is_some() {
+        return anyhow!(
+            "Contig {} not found in the header, which has {}; pass --normalize-contig to use it",
+            tid,
+            other
+        );
+    }
+    anyhow!(
+        "Contig {} not found in the header, available: {}",
+        tid,
+        listed
+    )
+}
+
+/// The other common name of a contig, `2` for `chr2` and `chr2` for `2`
+pub fn toggle_chr_prefix(tid: &str) -> String {
+    match tid.strip_prefix("chr") {
+        Some(bare) => bare.to_string(),
+        None => format!("chr{}", tid),
+    }
+}
+
+/// Reads seen and counted over a region, logged with the time it took at `-v`
+struct ReadCounts {
+    /// reads fetched from the index
+    seen: u64,
+    /// reads left once supplementary alignments and the read filter are applied
+    kept: u64,
+    /// counted reads whose name isn't UTF-8, read lossily
+    lossy_names: u64,
+    /// names of the read filters, with the reads each one rejected
+    rejected: Vec<(String, u64)>,
+    /// supplementary alignments are counted too
+    keep_supplementary: bool,
+    started: Instant,
+}
+
+impl ReadCounts {
+    fn new(read_filter: &impl ReadFilter, keep_supplementary: bool) -> Self {
+        Self {
+            seen: 0,
+            kept: 0,
+            lossy_names: 0,
+            rejected: read_filter
+                .names()
+                .into_iter()
+                .map(|name| (name, 0))
+                .collect(),
+            keep_supplementary,
+            started: Instant::now(),
+        }
+    }
+
+    // whether a fetched read is counted, tallying it against the filter rejecting it otherwise
+    fn keep(&mut self, read_filter: &impl ReadFilter, read: &Record) -> bool {
+        self.seen += 1;
+        if !self.keep_supplementary && read.is_supplementary() {
+            return false;
+        }
+        if let Some(index) = read_filter.rejected_by(read, None) {
+            if let Some((_, rejected)) = self.rejected.get_mut(index) {
+                *rejected += 1;
+            }
+            return false;
+        }
+        self.kept += 1;
+        if std::str::from_utf8(read.qname()).is_err() {
+            self.lossy_names += 1;
+        }
+        true
+    }
+
+    fn log(&self, tid: &str, start: u32, stop: u32) {
+        log::debug!(
+            "{}:{}-{}: {} of {} reads counted, {} filtered out, in {:.2?}",
+            tid,
+            start,
+            stop,
+            self.kept,
+            self.seen,
+            self.seen - self.kept,
+            self.started.elapsed()
+        );
+        let rejected: Vec<String> = self
+            .rejected
+            .iter()
+            .filter(|(_, rejected)| *rejected > 0)
+            .map(|(name, rejected)| format!("{} by {}", rejected, name))
+            .collect();
+        if !rejected.is_empty() {
+            log::debug!(
+                "{}:{}-{}: reads rejected {}",
+                tid,
+                start,
+                stop,
+                rejected.join(", ")
+            );
+        }
+        if self.lossy_names > 0 {
+            log::debug!(
+                "{}:{}-{}: {} read names are not UTF-8, replaced characters are shown",
+                tid,
+                start,
+                stop,
+                self.lossy_names
+            );
+        }
+    }
+}
+
+/// Add the counter at a position to the running depth sum before it; a depth can't be negative,
+/// so the sum saturates at zero
+fn add_depth(sum: i64, delta: i64) -> i64 {
+    (sum + delta).max(0)
+}
+
+/// Error on an empty or reversed region, which would leave nothing to count
+fn check_span(tid: &str, start: u32, stop: u32) -> Result<()> {
+    if start >= stop {
+        return Err(anyhow!(
+            "empty region {}:{}-{}, the start must be before the end",
+            tid,
+            start,
+            stop
+        ));
+    }
+    Ok(())
+}
+
+/// Fetch a region, with a readable error when its contig is not in the header
+fn fetch_region(reader: &mut bam::IndexedReader, tid: &str, start: u32, stop: u32) -> Result<()> {
+    log::trace!("fetching {}:{}-{}", tid, start, stop);
+    if reader.header().tid(tid.as_bytes()).is_none() {
+        return Err(missing_contig(reader.header(), tid));
+    }
+    reader
+        .fetch((tid, start, stop))
+        .map_err(|e| anyhow!("failed to fetch {}:{}-{}: {}", tid, start, stop, e))
+}
+
+/// Anything that implements RegionProcessor can compute the depths over a region.
+pub trait RegionProcessor {
+    /// Open an indexed reader over the input
+    fn open_reader(&self) -> Result<bam::IndexedReader>;
+
+    /// Look up the length of a contig in the header; if absent, return an error
+    fn contig_len(&self, tid: &str) -> Result<u32> {
+        let reader = self.open_reader()?;
+        let header = reader.header();
+        let len = header
+            .tid(tid.as_bytes())
+            .and_then(|id| header.target_len(id))
+            .ok_or_else(|| missing_contig(header, tid))?;
+        Ok(u32::try_from(len)?)
+    }
+
+    /// Name of a contig in the header: `tid` itself, or else `tid` with the `chr` prefix added
+    /// or removed; an error if neither is there
+    fn resolve_contig(&self, tid: &str) -> Result<String> {
+        let reader = self.open_reader()?;
+        let header = reader.header();
+        if header.tid(tid.as_bytes()).is_some() {
+            return Ok(tid.to_string());
+        }
+        let other = toggle_chr_prefix(tid);
+        if header.tid(other.as_bytes()).is_some() {
+            return Ok(other);
+        }
+        Err(missing_contig(header, tid))
+    }
+
+    /// List the read group IDs declared in the header
+    fn read_groups(&self) -> Result<Vec<String>> {
+        let reader = self.open_reader()?;
+        let header = bam::Header::from_template(reader.header());
+        Ok(header
+            .to_hashmap()
+            .get("RG")
+            .map(|groups| groups.iter().filter_map(|g| g.get("ID").cloned()).collect())
+            .unwrap_or_default())
+    }
+
+    /// List the samples (`SM`) of the read groups in the header with the IDs of their read
+    /// groups, in header order; a read group without a sample is a sample named by its ID
+    fn read_group_samples(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let reader = self.open_reader()?;
+        let header = bam::Header::from_template(reader.header());
+        let mut samples: Vec<(String, Vec<String>)> = Vec::new();
+        for group in header.to_hashmap().get("RG").into_iter().flatten() {
+            let Some(id) = group.get("ID") else {
+                continue;
+            };
+            let sample = group.get("SM").unwrap_or(id);
+            match samples.iter_mut().find(|(name, _)| name == sample) {
+                Some((_, ids)) => ids.push(id.clone()),
+                None => samples.push((sample.clone(), vec![id.clone()])),
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Count the mapped reads over all contigs from the index, without reading the alignments
+    fn mapped_reads(&self) -> Result<u64> {
+        let mut reader = self.open_reader()?;
+        let stats = reader.index_stats()?;
+        Ok(stats.iter().map(|(_, _, mapped, _)| mapped).sum())
+    }
+
+    /// Compute the depth at each position of a region with the number of reads counted
+    fn process_region(&self, tid: &str, start: u32, stop: u32) -> Result<RegionDepths> {
+        self.process_binned(tid, start, stop, Binning::default())
+    }
+
+    /// Compute the depths of a region merged into bins as they are summed
+    fn process_binned(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        binning: Binning,
+    ) -> Result<RegionDepths>;
+
+    /// Hand the depths of a region to `emit` as 0-based half-open `(start, end, depth)` ranges
+    /// of equal depth, in order.
+    ///
+    /// By default the whole region is computed first, processors that can sweep the reads
+    /// override it to keep only the reads over the current position in memory.
+    fn stream_region(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>,
+    ) -> Result<()> {
+        let mut sweep = RangeSweep::new(start, 1);
+        for pos in self.process_region(tid, start, stop)?.depths {
+            sweep.push(pos.pos, pos.pos + 1, pos.depth, emit)?;
+        }
+        sweep.flush(emit)
+    }
+}
+
+/// Depth of a region swept from left to right, holding only the blocks over the sweep position.
+///
+/// Blocks are added with [RangeSweep::add] once all blocks starting before the position given
+/// to [RangeSweep::advance_to] are known, and equal-depth ranges are merged before emitting.
+/// Each block adds its weight, the depth is the summed weight in whole `unit`s, rounded.
+struct RangeSweep {
+    /// first position not yet emitted
+    pos: u32,
+    /// weight of one read
+    unit: i64,
+    /// blocks waiting for the sweep to reach their start, with their weight
+    starts: BinaryHeap<Reverse<(u32, u32, i64)>>,
+    /// exclusive ends of the blocks over the sweep position, with their weight
+    ends: BinaryHeap<Reverse<(u32, i64)>>,
+    /// summed weight of the blocks over the sweep position
+    weight: i64,
+    /// range not yet emitted, in case the next one has the same depth
+    pending: Option<(u32, u32, u32)>,
+}
+
+impl RangeSweep {
+    fn new(start: u32, unit: i64) -> Self {
+        Self {
+            pos: start,
+            unit,
+            starts: BinaryHeap::new(),
+            ends: BinaryHeap::new(),
+            weight: 0,
+            pending: None,
+        }
+    }
+
+    // count a block from `start` to exclusive `end`, at or after the sweep position
+    fn add(&mut self, start: u32, end: u32, weight: i64) {
+        if start < end {
+            self.starts
+                .push(Reverse((start.max(self.pos), end, weight)));
+        }
+    }
+
+    // the summed weight over the sweep position in whole reads, rounded to the nearest
+    fn depth(&self) -> Result<u32> {
+        Ok(u32::try_from((self.weight + self.unit / 2) / self.unit)?)
+    }
+
+    // emit every range before `to`, applying the block starts and ends on the way
+    fn advance_to(
+        &mut self,
+        to: u32,
+        emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            let next_start = self.starts.peek().map(|Reverse((start, ..))| *start);
+            let next_end = self.ends.peek().map(|Reverse((end, _))| *end);
+            let event = match (next_start, next_end) {
+                (Some(start), Some(end)) => start.min(end),
+                (Some(pos), None) | (None, Some(pos)) => pos,
+                (None, None) => break,
+            };
+            if event > to {
+                break;
+            }
+            self.push(self.pos, event, self.depth()?, emit)?;
+            while let Some(&Reverse((_, weight))) =
+                self.ends.peek().filter(|Reverse((end, _))| *end == event)
+            {
+                self.weight -= weight;
+                self.ends.pop();
+            }
+            while let Some(&Reverse((_, end, weight))) = self
+                .starts
+                .peek()
+                .filter(|Reverse((start, ..))| *start == event)
+            {
+                self.weight += weight;
+                self.starts.pop();
+                self.ends.push(Reverse((end, weight)));
+            }
+        }
+        self.push(self.pos, to, self.depth()?, emit)
+    }
+
+    // queue the range from the sweep position, emitting the pending one if the depth changed
+    fn push(
+        &mut self,
+        start: u32,
+        end: u32,
+        depth: u32,
+        emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>,
+    ) -> Result<()> {
+        if start >= end {
+            return Ok(());
+        }
+        self.pos = end;
+        match self.pending {
+            Some((pending_start, pending_end, pending_depth))
+                if pending_end == start && pending_depth == depth =>
+            {
+                self.pending = Some((pending_start, end, depth));
+            }
+            pending => {
+                if let Some((pending_start, pending_end, pending_depth)) = pending {
+                    emit(pending_start, pending_end, pending_depth)?;
+                }
+                self.pending = Some((start, end, depth));
+            }
+        }
+        Ok(())
+    }
+
+    // emit the last range
+    fn flush(&mut self, emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>) -> Result<()> {
+        match self.pending.take() {
+            Some((start, end, depth)) => emit(start, end, depth),
+            None => Ok(()),
+        }
+    }
+}
+
+/// MAPQ thresholds [Tracks::usable] counts the depth at, from no threshold to the strictest
+pub const MAPQ_STEPS: [u8; 7] = [0, 1, 10, 20, 30, 40, 60];
+
+/// MAPQ from which a read weighs a whole read with [DepthProcessor::weight_by_mapq]
+pub const FULL_WEIGHT_MAPQ: u8 = 60;
+
+/// Columns shallower than this get no variant hint, a few odd reads are not a signal
+pub const MIN_HINT_DEPTH: usize = 4;
+
+/// Compute depth by counting where aligned blocks start and end, fast but deletions count as
+/// covered unless `del_as_gap` is set.
+pub struct DepthProcessor<F: ReadFilter + Send> {
     /// path to indexed BAM/CRAM
     pub reads: PathBuf,
-    /// implementation of [position::ReadFilter] that will be used
+    /// path to reference fasta, needed to decode CRAM
+    pub reference: Option<PathBuf>,
+    /// implementation of [ReadFilter] that will be used
     pub read_filter: F,
+    /// leave deleted reference bases uncovered, as introns (`N`) always are
+    pub del_as_gap: bool,
+    /// count supplementary alignments, off so the parts of a split read are counted once
+    pub count_supplementary: bool,
+    /// count each fragment once at a position, whatever its mates and supplementary parts
+    pub count_fragments: bool,
+    /// count each read as MAPQ / [FULL_WEIGHT_MAPQ] of a read, capped at one, the summed depth
+    /// rounded to whole reads and kept unrounded in [RegionDepths::weighted]
+    pub weight_by_mapq: bool,
+    /// tracks counted with the depth
+    pub tracks: Tracks,
+    /// bar advanced as the reads of each region are consumed
+    pub progress: Option<ProgressBar>,
 }
 
 impl<F: ReadFilter + Send> DepthProcessor<F> {
-    /// Create a new OnlyDepthProcessor
-    pub fn new(reads: PathBuf, read_filter: F) -> Self {
-        Self { reads, read_filter }
+    /// Create a new DepthProcessor
+    pub fn new(
+        reads: PathBuf,
+        reference: Option<PathBuf>,
+        read_filter: F,
+        del_as_gap: bool,
+    ) -> Self {
+        Self {
+            reads,
+            reference,
+            read_filter,
+            del_as_gap,
+            count_supplementary: false,
+            count_fragments: false,
+            weight_by_mapq: false,
+            tracks: Tracks::default(),
+            progress: None,
+        }
     }
 
-    /// Sum the counts within the region to get the depths at each RangePosition
-    #[inline]
-    fn sum_counter(
+    /// Counts one read adds to the counter, the depth is counted in units of [Self::unit]
+    fn weight(&self, read: &Record) -> i64 {
+        if self.weight_by_mapq {
+            i64::from(read.mapq().min(FULL_WEIGHT_MAPQ))
+        } else {
+            1
+        }
+    }
+
+    /// Counts of the counter making one read of depth
+    fn unit(&self) -> i64 {
+        if self.weight_by_mapq {
+            i64::from(FULL_WEIGHT_MAPQ)
+        } else {
+            1
+        }
+    }
+
+    /// Aligned blocks of a read, without the part already counted for its overlapping mate,
+    /// or for any alignment of its fragment with `count_fragments`
+    fn read_blocks(
         &self,
-        counter: Vec<i32>,
-        contig: &str,
-        region_start: u32,
-    ) -> Result<Vec<BedGraph>> {
-        let mut sum: i32 = 0;
-        let mut results = vec![];
-        for (i, count) in counter.iter().enumerate() {
-            sum += count;
-            let mut pos = BedGraph::new(String::from(contig), region_start + i as u32);
-            pos.depth = u32::try_from(sum).expect("All depths are positive");
-            results.push(pos);
+        read: &Rc<Record>,
+        mate_blocks: &mut HashMap<Vec<u8>, Vec<(i64, i64)>>,
+        fragments: &mut FragmentBlocks,
+    ) -> Vec<(i64, i64)> {
+        let blocks: Vec<(i64, i64)> = IterAlignedBlocks::new(read, self.del_as_gap).collect();
+
+        if self.count_fragments {
+            fragments.forget_before(read.pos());
+            return fragments.add(read.qname(), blocks);
         }
 
-        Ok(results)
+        // count the region shared by overlapping mates only once
+        if !is_mate_candidate(read) {
+            return blocks;
+        }
+        match mate_blocks.remove(read.qname()) {
+            Some(covered) => subtract_blocks(blocks, &covered),
+            None => {
+                if read.tid() == read.mtid()
+                    && read.mpos() >= read.pos()
+                    && read.mpos() < read.reference_end()
+                {
+                    mate_blocks.insert(read.qname().to_vec(), blocks.clone());
+                }
+                blocks
+            }
+        }
+    }
+
+    /// Tally of the reads of a region; the supplementary parts of a fragment are counted once
+    /// with the rest of it
+    fn read_counts(&self) -> ReadCounts {
+        ReadCounts::new(
+            &self.read_filter,
+            self.count_supplementary || self.count_fragments,
+        )
+    }
+
+    /// Depth of a summed count, weighted counts are rounded to the nearest whole read
+    #[inline]
+    fn depth_of(&self, sum: i64, contig: &str, pos: u32) -> Result<u32> {
+        let unit = self.unit();
+        u32::try_from((sum + unit / 2) / unit).map_err(|_| {
+            anyhow!(
+                "depth {} at {}:{} does not fit in 32 bits",
+                sum,
+                contig,
+                pos
+            )
+        })
+    }
+}
+
+impl<F: ReadFilter + Send> RegionProcessor for DepthProcessor<F> {
+    fn open_reader(&self) -> Result<bam::IndexedReader> {
+        open_indexed_reader(&self.reads, self.reference.as_deref())
     }
 
     /// Process a region, taking into account REF_SKIPs and mates
-    pub fn process_region(&self, tid: &str, start: u32, stop: u32) -> Result<Vec<BedGraph>> {
+    ///
+    /// Where the two mates of a pair overlap, the shared positions are only counted once.
+    fn process_binned(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        binning: Binning,
+    ) -> Result<RegionDepths> {
+        check_span(tid, start, stop)?;
         // Create a reader
-        let mut reader = bam::IndexedReader::from_path(&self.reads)?;
+        let mut reader = self.open_reader()?;
 
         // fetch the region of interest
-        reader.fetch((tid, start, stop))?;
+        fetch_region(&mut reader, tid, start, stop)?;
+
+        // one extra slot past the region end, so every interval has somewhere to count its end
+        let len = (stop - start) as usize;
+        let mut counter: Vec<i64> = vec![0; len + 1];
+        let mut reverse_counter = self.tracks.reverse.then(|| vec![0i64; len + 1]);
+        // MAPQ of the reads and the reads themselves, counted whole even when weighted
+        let mut mapq_counters = self
+            .tracks
+            .mapq
+            .then(|| (vec![0i64; len + 1], vec![0i64; len + 1]));
+        let mut softclips = self.tracks.softclips.then(BTreeMap::new);
+        // starts and ends of the reads whose MAPQ reaches each step past the first but not the
+        // next one, the reads of the first step are all in the depth counter
+        let mut usable_counters = self
+            .tracks
+            .usable
+            .then(|| vec![vec![0i64; len + 1]; MAPQ_STEPS.len() - 1]);
 
-        let mut counter: Vec<i32> = vec![0; (stop - start) as usize];
+        // blocks of first-seen mates that overlap their mate, keyed by qname
+        let mut mate_blocks: HashMap<Vec<u8>, Vec<(i64, i64)>> = HashMap::new();
+        let mut fragments = FragmentBlocks::default();
 
         // Walk over each read, counting the starts and ends
-        for record in reader
-            .rc_records()
-            .map(|r| r.unwrap())
-            .filter(|read| self.read_filter.filter_read(read, None))
-            .flat_map(IterAlignedBlocks::new)
-        {
-            let rec_start = u32::try_from(record.0)?;
-            let rec_stop = u32::try_from(record.1)?;
+        let mut counts = self.read_counts();
+        let mut progress = RegionProgress::new(self.progress.as_ref(), start, stop);
+        for read in reader.rc_records() {
+            let read = read?;
+            if !counts.keep(&self.read_filter, &read) {
+                continue;
+            }
+            progress.advance_to(read.pos());
+            if let Some(softclips) = softclips.as_mut() {
+                count_softclips(softclips, start, stop, &read);
+            }
+            let weight = self.weight(&read);
+            let mapq = i64::from(read.mapq());
+            let on_reverse_track = reverse_counter.is_some() && read.is_reverse();
+            let step = MAPQ_STEPS
+                .iter()
+                .rposition(|&step| read.mapq() >= step)
+                .unwrap_or(0);
+            for (block_start, block_stop) in
+                self.read_blocks(&read, &mut mate_blocks, &mut fragments)
+            {
+                let rec_start = u32::try_from(block_start)?;
+                let rec_stop = u32::try_from(block_stop)?;
+
+                // NB: since we are splitting the region, it's possible the region we are looking at
+                // may occur before the ROI, or after the ROI
+                if rec_start >= stop || rec_stop <= start {
+                    continue;
+                }
+
+                // rectify start / stop with region boundaries, the stop is exclusive
+                // so an interval reaching past the region counts its end in the extra slot
+                let adjusted_start = (rec_start.max(start) - start) as usize;
+                let adjusted_stop = (rec_stop.min(stop) - start) as usize;
+
+                let counter = match reverse_counter.as_mut() {
+                    Some(reverse_counter) if on_reverse_track => reverse_counter,
+                    _ => &mut counter,
+                };
+                counter[adjusted_start] += weight;
+                counter[adjusted_stop] -= weight;
+                if let Some(usable_counters) = usable_counters.as_mut() {
+                    if step > 0 && !on_reverse_track {
+                        usable_counters[step - 1][adjusted_start] += weight;
+                        usable_counters[step - 1][adjusted_stop] -= weight;
+                    }
+                }
+                if let Some((mapq_counter, read_counter)) = mapq_counters.as_mut() {
+                    mapq_counter[adjusted_start] += mapq;
+                    mapq_counter[adjusted_stop] -= mapq;
+                    read_counter[adjusted_start] += 1;
+                    read_counter[adjusted_stop] -= 1;
+                }
+            }
+        }
+
+        counts.log(tid, start, stop);
+
+        // telling bases apart needs the pileup
+        let tracks = Tracks {
+            hints: false,
+            ..self.tracks
+        };
+        // Sum the counters position by position into the bins, without the extra slot that only
+        // holds ends beyond the region; a depth can't be negative, so its sum saturates at zero
+        let mut binned = BinnedTracks::new(tracks, self.weight_by_mapq, binning, start, stop);
+        let (mut sum, mut reverse_sum, mut mapq_sum, mut read_sum) = (0i64, 0i64, 0i64, 0i64);
+        let mut step_sums = [0i64; MAPQ_STEPS.len() - 1];
+        let mut column = Column::default();
+        for i in 0..len {
+            let pos = start + i as u32;
+            sum = add_depth(sum, counter[i]);
+            column.depth = self.depth_of(sum, tid, pos)?;
+            if let Some(reverse_counter) = &reverse_counter {
+                reverse_sum = add_depth(reverse_sum, reverse_counter[i]);
+                column.reverse = self.depth_of(reverse_sum, tid, pos)?;
+            }
+            if let Some((mapq_counter, read_counter)) = &mapq_counters {
+                mapq_sum += mapq_counter[i];
+                read_sum += read_counter[i];
+                column.mapq = mapq_sum.checked_div(read_sum).unwrap_or(0) as u32;
+            }
+            // a read reaching a step reaches every looser one, so the steps are summed from the
+            // strictest down
+            if let Some(usable_counters) = &usable_counters {
+                column.usable[0] = column.depth;
+                let mut stricter = 0;
+                for (step, counter) in usable_counters.iter().enumerate().rev() {
+                    step_sums[step] += counter[i];
+                    stricter += step_sums[step];
+                    column.usable[step + 1] = self.depth_of(stricter.max(0), tid, pos)?;
+                }
+            }
+            column.weighted = (sum + reverse_sum) as f64 / self.unit() as f64;
+            binned.push(&column);
+        }
+        Ok(binned.finish(tid, counts.kept, softclips))
+    }
 
-            // NB: since we are splitting the region, it's possible the region we are looking at
-            // may occur before the ROI, or after the ROI
-            if rec_start >= stop || rec_stop <= start {
+    /// Sweep the reads in position order, so only the blocks over the current position are held
+    fn stream_region(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        emit: &mut dyn FnMut(u32, u32, u32) -> Result<()>,
+    ) -> Result<()> {
+        check_span(tid, start, stop)?;
+        let mut reader = self.open_reader()?;
+        fetch_region(&mut reader, tid, start, stop)?;
+
+        let mut mate_blocks: HashMap<Vec<u8>, Vec<(i64, i64)>> = HashMap::new();
+        let mut fragments = FragmentBlocks::default();
+        let mut sweep = RangeSweep::new(start, self.unit());
+        let mut counts = self.read_counts();
+        let mut progress = RegionProgress::new(self.progress.as_ref(), start, stop);
+        for read in reader.rc_records() {
+            let read = read?;
+            if !counts.keep(&self.read_filter, &read) {
                 continue;
             }
+            // reads come sorted by start, so no later block starts before this read
+            let read_start = u32::try_from(read.pos().max(0))?.clamp(start, stop);
+            sweep.advance_to(read_start, emit)?;
+            progress.advance_to(read.pos());
+            let weight = self.weight(&read);
+            for (block_start, block_stop) in
+                self.read_blocks(&read, &mut mate_blocks, &mut fragments)
+            {
+                let rec_start = u32::try_from(block_start)?;
+                let rec_stop = u32::try_from(block_stop)?;
+                sweep.add(
+                    rec_start.clamp(start, stop),
+                    rec_stop.clamp(start, stop),
+                    weight,
+                );
+            }
+        }
+        sweep.advance_to(stop, emit)?;
+        counts.log(tid, start, stop);
+        sweep.flush(emit)
+    }
+}
 
-            // rectify start / stop with region boundaries
-            // increment the start of the region
-            let adjusted_start = if rec_start < start {
-                0
-            } else {
-                (rec_start - start) as usize
-            };
+/// Compute true per-base depth with the htslib pileup engine, like `samtools depth -a`.
+///
+/// Slower than [DepthProcessor], but deletions and reference skips are not counted as covered.
+pub struct PileupDepthProcessor<F: ReadFilter + Send> {
+    /// path to indexed BAM/CRAM
+    pub reads: PathBuf,
+    /// path to reference fasta, needed to decode CRAM
+    pub reference: Option<PathBuf>,
+    /// implementation of [ReadFilter] that will be used
+    pub read_filter: F,
+    /// bases with a lower quality are not counted
+    pub min_baseq: u8,
+    /// count supplementary alignments, off so the parts of a split read are counted once
+    pub count_supplementary: bool,
+    /// tracks counted with the depth
+    pub tracks: Tracks,
+    /// bar advanced as the columns of each region are walked
+    pub progress: Option<ProgressBar>,
+}
 
-            let mut dont_count_stop = false; // if this interval extends past the end of the region, don't count an end for it
-            let adjusted_stop = if rec_stop >= stop {
-                dont_count_stop = true;
-                counter.len() - 1
-            } else {
-                (rec_stop - start) as usize
-            };
+impl<F: ReadFilter + Send> PileupDepthProcessor<F> {
+    /// Create a new PileupDepthProcessor
+    pub fn new(reads: PathBuf, reference: Option<PathBuf>, read_filter: F, min_baseq: u8) -> Self {
+        Self {
+            reads,
+            reference,
+            read_filter,
+            min_baseq,
+            count_supplementary: false,
+            tracks: Tracks::default(),
+            progress: None,
+        }
+    }
+}
 
-            counter[adjusted_start] += 1;
-            if !dont_count_stop {
-                // check if the end of interval extended past region end
-                counter[adjusted_stop] -= 1;
+impl<F: ReadFilter + Send> RegionProcessor for PileupDepthProcessor<F> {
+    fn open_reader(&self) -> Result<bam::IndexedReader> {
+        open_indexed_reader(&self.reads, self.reference.as_deref())
+    }
+
+    /// Process a region by walking the pileup column by column
+    fn process_binned(
+        &self,
+        tid: &str,
+        start: u32,
+        stop: u32,
+        binning: Binning,
+    ) -> Result<RegionDepths> {
+        check_span(tid, start, stop)?;
+        let mut reader = self.open_reader()?;
+        fetch_region(&mut reader, tid, start, stop)?;
+
+        // the steps are only counted from blocks, and only blocks are weighted
+        let tracks = Tracks {
+            usable: false,
+            ..self.tracks
+        };
+        let mut binned = BinnedTracks::new(tracks, false, binning, start, stop);
+        let mut softclips = self.tracks.softclips.then(BTreeMap::new);
+        let mut counts = ReadCounts::new(&self.read_filter, self.count_supplementary);
+        let mut progress = RegionProgress::new(self.progress.as_ref(), start, stop);
+
+        // the pileup of htslib only drops unmapped reads, unlike samtools it has no default mask
+        // of duplicate, secondary and QC-failed reads, so --include-flags and --incl-dups apply
+        let mut pileups = reader.pileup();
+        // don't cap the depth, like samtools depth
+        pileups.set_max_depth(i32::MAX as u32);
+        for pileup in pileups {
+            let pileup = pileup?;
+            let pos = pileup.pos();
+            progress.advance_to(i64::from(pos));
+            // each read is counted at its first column, which may be left of the region
+            for aln in pileup.alignments().filter(|aln| aln.is_head()) {
+                let record = aln.record();
+                if !counts.keep(&self.read_filter, &record) {
+                    continue;
+                }
+                if let Some(softclips) = softclips.as_mut() {
+                    count_softclips(softclips, start, stop, &record);
+                }
+            }
+            // the pileup also yields columns of reads hanging over the region
+            if pos < start || pos >= stop {
+                continue;
             }
+            let (mut forward_depth, mut reverse_depth, mut mapq_sum) = (0u32, 0u32, 0u64);
+            // reads over the column with their indels and compared bases, for the hints
+            let (mut hint_depth, mut indels) = (0, 0);
+            let mut bases: HashMap<u8, usize> = HashMap::new();
+            for aln in pileup.alignments() {
+                if aln.is_refskip() {
+                    continue;
+                }
+                let record = aln.record();
+                if !self.count_supplementary && record.is_supplementary() {
+                    continue;
+                }
+                if !self.read_filter.filter_read(&record, Some(&aln)) {
+                    continue;
+                }
+                let passes_baseq = aln
+                    .qpos()
+                    .is_some_and(|qpos| record.qual()[qpos] >= self.min_baseq);
+                if self.tracks.hints {
+                    hint_depth += 1;
+                    if aln.is_del() || !matches!(aln.indel(), Indel::None) {
+                        indels += 1;
+                    } else if let (true, Some(qpos)) = (passes_baseq, aln.qpos()) {
+                        *bases.entry(record.seq()[qpos]).or_default() += 1;
+                    }
+                }
+                if aln.is_del() || !passes_baseq {
+                    continue;
+                }
+                if self.tracks.reverse && record.is_reverse() {
+                    reverse_depth += 1;
+                } else {
+                    forward_depth += 1;
+                }
+                mapq_sum += u64::from(record.mapq());
+            }
+            let mut column = Column {
+                depth: forward_depth,
+                reverse: reverse_depth,
+                mapq: mapq_sum
+                    .checked_div(u64::from(forward_depth + reverse_depth))
+                    .unwrap_or(0) as u32,
+                ..Default::default()
+            };
+            if hint_depth >= MIN_HINT_DEPTH {
+                let compared: usize = bases.values().sum();
+                let common = bases.values().copied().max().unwrap_or(0);
+                let disagreeing = (indels + compared - common).min(hint_depth);
+                column.hint = (disagreeing * 100 / hint_depth) as u32;
+            }
+            // the pileup skips the columns no read covers
+            binned.fill_to(pos);
+            binned.push(&column);
+        }
+        binned.fill_to(stop);
+        counts.log(tid, start, stop);
+
+        Ok(binned.finish(tid, counts.kept, softclips))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_htslib::bam::header::{Header, HeaderRecord};
+    use rust_htslib::bam::HeaderView;
+    use std::fs;
+
+    // a SAM line of a read on contig `1` with a MAPQ of 60, all bases A of quality 40
+    fn sam(qname: &str, flag: u16, pos: u32, cigar: &str, len: usize) -> String {
+        format!(
+            "{}\t{}\t1\t{}\t60\t{}\t=\t{}\t0\t{}\t{}",
+            qname,
+            flag,
+            pos,
+            cigar,
+            pos,
+            "A".repeat(len),
+            "I".repeat(len)
+        )
+    }
+
+    // a header of the one 1000 bp contig `1`
+    fn header() -> Header {
+        let mut header = Header::new();
+        header.push_record(
+            HeaderRecord::new(b"SQ")
+                .push_tag(b"SN", "1")
+                .push_tag(b"LN", 1000),
+        );
+        header
+    }
+
+    // a record parsed from a SAM line
+    fn record(line: &str) -> Record {
+        Record::from_sam(&HeaderView::from_header(&header()), line.as_bytes()).unwrap()
+    }
+
+    // an indexed BAM of the sorted `lines` over a 1000 bp contig `1`, in a directory per test run
+    fn write_bam(name: &str, lines: &[String]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tcov-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.bam", name));
+        let header = header();
+        let view = HeaderView::from_header(&header);
+        let mut writer = bam::Writer::from_path(&path, &header, bam::Format::Bam).unwrap();
+        for line in lines {
+            writer
+                .write(&Record::from_sam(&view, line.as_bytes()).unwrap())
+                .unwrap();
         }
+        drop(writer);
+        bam::index::build(&path, None, bam::index::Type::Bai, 1).unwrap();
+        path
+    }
+
+    fn depths(counted: &RegionDepths) -> Vec<u32> {
+        counted.depths.iter().map(|pos| pos.depth).collect()
+    }
+
+    #[test]
+    fn pileup_keeps_flags_the_filter_lets_through() {
+        let bam = write_bam(
+            "pileup_flags",
+            &[
+                sam("primary", 0, 11, "10M", 10),
+                sam("duplicate", 0x400, 11, "10M", 10),
+                sam("secondary", 0x100, 11, "10M", 10),
+            ],
+        );
+        let pileup = |include_flags, exclude_flags| {
+            let filter = DefaultReadFilter::new(include_flags, exclude_flags, 0);
+            let processor = PileupDepthProcessor::new(bam.clone(), None, filter, 0);
+            depths(&processor.process_region("1", 10, 12).unwrap())
+        };
+        assert_eq!(pileup(0, 0), vec![3, 3]);
+        assert_eq!(pileup(0, 0x400), vec![2, 2]);
+        assert_eq!(pileup(0x100, 0), vec![1, 1]);
+    }
+
+    #[test]
+    fn read_past_stop_is_cut_at_the_region_end() {
+        let bam = write_bam("past_stop", &[sam("long", 0, 11, "100M", 100)]);
+        let filter = DefaultReadFilter::new(0, 0, 0);
+        let processor = DepthProcessor::new(bam, None, filter, false);
+        let counted = processor.process_region("1", 10, 20).unwrap();
+        assert_eq!(depths(&counted), vec![1; 10]);
+        let mut ranges = Vec::new();
+        processor
+            .stream_region("1", 10, 20, &mut |start, end, depth| {
+                ranges.push((start, end, depth));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(ranges, vec![(10, 20, 1)]);
+    }
+
+    #[test]
+    fn subtract_blocks_keeps_what_the_mate_left_uncovered() {
+        // full overlap
+        assert_eq!(subtract_blocks(vec![(10, 20)], &[(5, 25)]), vec![]);
+        // partial overlap
+        assert_eq!(subtract_blocks(vec![(10, 20)], &[(15, 25)]), vec![(10, 15)]);
+        // the block falls in a gap between the mate's blocks
+        assert_eq!(
+            subtract_blocks(vec![(10, 20)], &[(0, 5), (30, 40)]),
+            vec![(10, 20)]
+        );
+        // a spliced mate covers both ends of one block and the start of the next
+        assert_eq!(
+            subtract_blocks(vec![(10, 20), (30, 40)], &[(5, 12), (18, 32)]),
+            vec![(12, 18), (32, 40)]
+        );
+    }
+
+    #[test]
+    fn depth_of_saturates_at_zero_and_errors_on_overflow() {
+        let filter = DefaultReadFilter::new(0, 0, 0);
+        let processor = DepthProcessor::new(PathBuf::new(), None, filter, false);
+        // a counter whose running sum would dip below zero
+        let sums: Vec<i64> = [1, -1, -3, 2]
+            .iter()
+            .scan(0, |sum, &delta| {
+                *sum = add_depth(*sum, delta);
+                Some(*sum)
+            })
+            .collect();
+        assert_eq!(sums, vec![1, 0, 0, 2]);
+        let depths: Vec<u32> = sums
+            .iter()
+            .map(|&sum| processor.depth_of(sum, "1", 5).unwrap())
+            .collect();
+        assert_eq!(depths, vec![1, 0, 0, 2]);
+        assert_eq!(processor.depth_of(7, "1", 5).unwrap(), 7);
+        let err = processor
+            .depth_of(i64::from(u32::MAX) + 1, "1", 5)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "depth 4294967296 at 1:5 does not fit in 32 bits"
+        );
+    }
+
+    #[test]
+    fn merge_positions_joins_equal_neighbours() {
+        let pos = |ref_seq: &str, pos: u32, depth: u32| {
+            let mut bedgraph = BedGraph::new(ref_seq.to_string(), pos);
+            bedgraph.depth = depth;
+            bedgraph
+        };
+        let range = |ref_seq: &str, start: u32, end: u32, depth: u32| RangePosition {
+            ref_seq: ref_seq.to_string(),
+            start,
+            end,
+            depth,
+        };
+        assert_eq!(merge_positions(&[]), vec![]);
+        // equal depths
+        assert_eq!(
+            merge_positions(&[pos("1", 10, 2), pos("1", 11, 2), pos("1", 12, 2)]),
+            vec![range("1", 10, 13, 2)]
+        );
+        // a depth change
+        assert_eq!(
+            merge_positions(&[pos("1", 10, 2), pos("1", 11, 3)]),
+            vec![range("1", 10, 11, 2), range("1", 11, 12, 3)]
+        );
+        // a contig change
+        assert_eq!(
+            merge_positions(&[pos("1", 10, 2), pos("2", 11, 2)]),
+            vec![range("1", 10, 11, 2), range("2", 11, 12, 2)]
+        );
+        // a gap
+        assert_eq!(
+            merge_positions(&[pos("1", 10, 2), pos("1", 12, 2)]),
+            vec![range("1", 10, 11, 2), range("1", 12, 13, 2)]
+        );
+    }
+
+    #[test]
+    fn min_baseq_drops_low_quality_bases() {
+        let depths_at = |min_baseq| {
+            let filter = DefaultReadFilter::new(0, 0, 0);
+            let bam = PathBuf::from("data/test.bam");
+            let processor = PileupDepthProcessor::new(bam, None, filter, min_baseq);
+            depths(&processor.process_region("2", 2078886, 2078891).unwrap())
+        };
+        assert_eq!(depths_at(0), vec![27, 27, 30, 33, 32]);
+        assert_eq!(depths_at(30), vec![24, 23, 26, 33, 30]);
+    }
+
+    #[test]
+    fn aligned_blocks_cover_deletions_unless_gaps() {
+        let read = record(&sam("deleted", 0, 101, "10M5D10M", 20));
+        let blocks = |del_as_gap| IterAlignedBlocks::new(&read, del_as_gap).collect::<Vec<_>>();
+        assert_eq!(blocks(false), vec![(100, 110), (110, 115), (115, 125)]);
+        assert_eq!(blocks(true), vec![(100, 110), (115, 125)]);
+    }
+
+    #[test]
+    fn supplementary_part_of_a_split_read_is_skipped() {
+        let primary = record(&sam("split", 0, 11, "10M10S", 20));
+        let supplementary = record(&sam("split", 0x800, 51, "10S10M", 20));
+        let filter = DefaultReadFilter::new(0, 0, 0);
+        let mut counts = ReadCounts::new(&filter, false);
+        assert!(counts.keep(&filter, &primary));
+        assert!(!counts.keep(&filter, &supplementary));
+        assert_eq!((counts.seen, counts.kept), (2, 1));
+        let mut counts = ReadCounts::new(&filter, true);
+        assert!(counts.keep(&filter, &primary));
+        assert!(counts.keep(&filter, &supplementary));
+        assert_eq!((counts.seen, counts.kept), (2, 2));
+    }
+
+    #[test]
+    fn missing_contig_lists_the_header_and_empty_region_is_flat() {
+        let bam = write_bam("contigs", &[sam("read", 0, 11, "10M", 10)]);
+        let filter = DefaultReadFilter::new(0, 0, 0);
+        let processor = DepthProcessor::new(bam, None, filter, false);
+        let err = processor.process_region("9", 10, 20).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Contig 9 not found in the header, available: 1"
+        );
+        let err = processor.process_region("chr1", 10, 20).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Contig chr1 not found in the header, which has 1; pass --normalize-contig to use it"
+        );
+        // no read over the region is a zero track, not an error
+        let counted = processor.process_region("1", 500, 510).unwrap();
+        assert_eq!(depths(&counted), vec![0; 10]);
+        assert_eq!(counted.reads, 0);
+    }
+
+    #[test]
+    fn proper_pair_filter_drops_only_improper_pairs() {
+        let proper = record(&sam("proper", 0x1 | 0x2, 11, "10M", 10));
+        let improper = record(&sam("improper", 0x1, 11, "10M", 10));
+        let single = record(&sam("single", 0, 11, "10M", 10));
+        assert!(ProperPairFilter.filter_read(&proper, None));
+        assert!(!ProperPairFilter.filter_read(&improper, None));
+        // single-end reads have no pair to be improper about
+        assert!(ProperPairFilter.filter_read(&single, None));
+    }
+
+    #[test]
+    fn empty_or_reversed_region_is_an_error() {
+        let bam = write_bam("span", &[sam("read", 0, 11, "10M", 10)]);
+        let processor = DepthProcessor::new(bam, None, DefaultReadFilter::new(0, 0, 0), false);
+        let err = processor.process_region("1", 20, 10).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "empty region 1:20-10, the start must be before the end"
+        );
+        assert!(processor.process_region("1", 10, 10).is_err());
+        let streamed = processor.stream_region("1", 20, 10, &mut |_, _, _| Ok(()));
+        assert!(streamed.is_err());
+    }
 
-        // Sum the counter and merge same-depth ranges of positions
-        self.sum_counter(counter, tid, start)
+    #[test]
+    fn overlapping_mates_are_counted_once() {
+        // a proper pair whose mates share 1:16-20, the mate position in the 8th column
+        let mate = |flag: u16, pos: u32, mate_pos: u32| {
+            sam("pair", flag, pos, "10M", 10).replacen(
+                &format!("=\t{}\t", pos),
+                &format!("=\t{}\t", mate_pos),
+                1,
+            )
+        };
+        let pair = write_bam(
+            "overlapping_mates",
+            &[
+                mate(0x1 | 0x2 | 0x20, 11, 16),
+                mate(0x1 | 0x2 | 0x10, 16, 11),
+            ],
+        );
+        let processor = DepthProcessor::new(pair, None, DefaultReadFilter::new(0, 0, 0), false);
+        let counted = processor.process_region("1", 8, 27).unwrap();
+        assert_eq!(
+            depths(&counted),
+            [vec![0; 2], vec![1; 15], vec![0; 2]].concat()
+        );
+        assert_eq!(counted.reads, 2);
+        // the same reads unpaired overlap twice
+        let unpaired = write_bam(
+            "overlapping_singles",
+            &[
+                sam("pair", 0, 11, "10M", 10),
+                sam("pair", 0x10, 16, "10M", 10),
+            ],
+        );
+        let processor = DepthProcessor::new(unpaired, None, DefaultReadFilter::new(0, 0, 0), false);
+        assert_eq!(
+            depths(&processor.process_region("1", 8, 27).unwrap()),
+            [vec![0; 2], vec![1; 5], vec![2; 5], vec![1; 5], vec![0; 2]].concat()
+        );
     }
 }
@@ -0,0 +1,70 @@
+//! The coverage engine behind tcov, usable as a library.
+//!
+//! ```no_run
+//! use tcov::cov::DefaultReadFilter;
+//!
+//! let read_filter = DefaultReadFilter::new(0, 0, 0);
+//! let depths = tcov::depth_of_region("data/test.bam", "2:2078887-2079669", read_filter)?;
+//! for pos in depths {
+//!     println!("{}", pos);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+pub mod annotation;
+pub mod baseline;
+pub mod cache;
+pub mod cov;
+pub mod output;
+pub mod progress;
+pub mod reference;
+pub mod region;
+pub mod stats;
+pub mod variant;
+
+use anyhow::Result;
+use cov::{BedGraph, DepthProcessor, ReadFilter, RegionProcessor};
+use region::{parse_region, Target};
+use std::path::Path;
+
+/// Compute the depth at each position of a region in an indexed BAM.
+///
+/// The region is formatted as in `--region`, 1-based inclusive by default; a bare contig means
+/// the whole contig, and an end past the contig end is clamped to it. A region starting at or
+/// past the contig end is an error.
+/// CRAM input needs a reference, use [DepthProcessor] directly for it.
+pub fn depth_of_region<F: ReadFilter + Send>(
+    bam: impl AsRef<Path>,
+    region: &str,
+    read_filter: F,
+) -> Result<Vec<BedGraph>> {
+    let (chrom, start, end) = parse_region(region, 1)?;
+    let processor = DepthProcessor::new(bam.as_ref().to_path_buf(), None, read_filter, false);
+    let mut target = Target {
+        chrom,
+        start,
+        end,
+        name: None,
+    };
+    target.clamp_to_contig(processor.contig_len(&target.chrom)?, 1)?;
+    Ok(processor
+        .process_region(&target.chrom, target.start, target.end)?
+        .depths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cov::DefaultReadFilter;
+
+    #[test]
+    fn region_starting_past_the_contig_end_is_an_error() {
+        let read_filter = DefaultReadFilter::new(0, 0, 0);
+        let err = depth_of_region("data/test.bam", "2:300000000-300000100", read_filter)
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("region 2:300000000-"));
+        assert!(err
+            .to_string()
+            .contains("starts past the end of the contig"));
+    }
+}
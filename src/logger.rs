@@ -0,0 +1,38 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Messages on stderr, info plain and the other levels prefixed with theirs
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Info => eprintln!("{}", record.args()),
+            level => eprintln!("{}: {}", level.as_str().to_lowercase(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Log to stderr, errors only with `quiet`, otherwise info and more with each `-v`
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    // only fails if a logger is already set, which then keeps logging
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
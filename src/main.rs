@@ -1,51 +1,1104 @@
 mod cli;
-mod cov;
+mod logger;
+mod plot;
+mod svg;
+mod track;
 mod tui;
-use anyhow::{anyhow, Result};
-use cli::parse_cli;
-use cov::{DefaultReadFilter, DepthProcessor};
-use std::path::PathBuf;
+use anyhow::{anyhow, Context, Result};
+use cli::{parse_cli, Command, DepthUnit, FailUnder, Format, Mode, Normalize, Strand, Weight};
+use plot::PlotSample;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tcov::annotation::{find_feature, load_features};
+use tcov::baseline::load_baseline;
+use tcov::cache::CachedProcessor;
+use tcov::cov::{
+    build_missing_index, remote_url, AndFilter, Binning, DefaultReadFilter, DepthProcessor,
+    EditDistanceFilter, InsertSizeFilter, PileupDepthProcessor, ProperPairFilter, ReadGroupFilter,
+    ReadLengthFilter, RegionDepths, RegionProcessor, Tracks, MAPQ_STEPS,
+};
+use tcov::output;
+use tcov::progress::progress_bar;
+use tcov::reference::{gc_percent, n_percent, Reference};
+use tcov::region::{parse_bed, parse_position, parse_region, read_regions, Target};
+use tcov::stats::{covered_bases, depth_stats, CoverageSummary};
+use tcov::variant::fetch_variants;
+use tui::{LiveFilters, RegionCov, SampleCov, ViewOptions};
+
+/// One stacked track of the view, the reads of a file or of some of its read groups
+struct Input {
+    bam: String,
+    /// shown beside the track
+    name: String,
+    /// read groups whose reads are counted, all of them if empty
+    read_groups: Vec<String>,
+}
+
+/// SAM flag of reads on the reverse strand
+const REVERSE: u16 = 0x10;
+
+/// SAM flag of PCR or optical duplicates
+const DUPLICATE: u16 = 0x400;
+
+/// SAM flags by the names samtools gives them
+const FLAG_NAMES: [(u16, &str); 12] = [
+    (0x1, "PAIRED"),
+    (0x2, "PROPER_PAIR"),
+    (0x4, "UNMAP"),
+    (0x8, "MUNMAP"),
+    (0x10, "REVERSE"),
+    (0x20, "MREVERSE"),
+    (0x40, "READ1"),
+    (0x80, "READ2"),
+    (0x100, "SECONDARY"),
+    (0x200, "QCFAIL"),
+    (0x400, "DUP"),
+    (0x800, "SUPPLEMENTARY"),
+];
 
 fn main() -> Result<()> {
     // parse cli
     let cli = parse_cli()?;
-    let bam = cli.bam;
-    let region = cli.region;
+    logger::init(cli.verbose, cli.quiet);
+    // a query reads a single base, not worth a bar
+    let progress = progress_bar(cli.quiet || cli.command.is_some());
+    let bams = cli.bam;
+    let regions = cli.region;
+    let coord_base = cli.coord_base;
+    let normalize_contig = cli.normalize_contig;
+    let pad = cli.pad;
+    let bed = cli.bed;
+    let reference = cli.reference.map(PathBuf::from);
+    let vcf = cli.vcf;
+    let annotation = cli.annotation;
+    let baseline = cli.baseline;
+    let build_index = cli.build_index;
     let color = cli.color;
+    let reverse_color = cli.reverse_color;
+    let label_color = cli.label_color;
+    let colormap = cli.colormap;
+    let chart = cli.chart;
     let step_size = cli.step_size;
+    let flip = cli.flip;
+    let log = cli.log;
+    let stats = cli.stats;
+    let min_cov = cli.min_cov;
+    let max_depth = cli.max_depth;
+    let hist_bins = cli.hist_bins;
+    let min_breadth_depth = cli.min_breadth_depth;
+    let gc = cli.gc;
+    let mask_n = cli.mask_n;
+    let mapq_track = cli.mapq_track;
+    let usable_mapq = cli.usable_mapq;
+    let softclip_track = cli.softclip_track;
+    let variant_hints = cli.variant_hints;
+    let cache_dir = cli.cache_dir.map(PathBuf::from);
+    let session = cli.session.map(PathBuf::from);
+    let tick_ms = cli.tick_ms;
+    let redraw_on_event = cli.redraw_on_event;
+    let mouse = !cli.no_mouse;
+    let bin_size = cli.bin_size;
+    let bin_stat = cli.bin_stat;
+    let smooth = cli.smooth;
+    let mode = cli.mode;
+    let depth_unit = cli.depth_unit;
+    let weight = cli.weight;
+    let strand = cli.strand;
+    let threads = cli.threads;
+    let normalize = cli.normalize;
+    let output = cli.output;
+    let no_tui = cli.no_tui;
+    let dry_run = cli.dry_run;
+    let format = cli.format;
+    let json_depths = cli.json_depths;
+    let fail_under = cli.fail_under;
+    let screenshot = cli.screenshot;
+    let screenshot_dir = cli.screenshot_dir;
+    let plot_size = (cli.plot_width, cli.plot_height);
     let include_flags = cli.include_flags;
-    let exclude_flags = cli.exclude_flags;
+    // duplicates only count when asked for
+    let exclude_flags = if cli.incl_dups {
+        cli.exclude_flags
+    } else {
+        cli.exclude_flags | DUPLICATE
+    };
     let min_mapq = cli.min_mapq;
+    let min_baseq = cli.min_baseq;
+    let del_as_gap = cli.del_as_gap;
+    let min_insert = cli.min_insert;
+    let max_insert = cli.max_insert;
+    let proper_pairs_only = cli.proper_pairs_only;
+    let max_nm = cli.max_nm;
+    let drop_missing_nm = cli.drop_missing_nm;
+    let min_read_len = cli.min_read_len;
+    let max_read_len = cli.max_read_len;
+    let read_groups = cli.read_group;
+    let split_by_rg = cli.split_by_rg;
+    let count_supplementary = cli.count_supplementary;
+    let command = cli.command;
+
+    if depth_unit == DepthUnit::fragment && matches!(mode, Mode::pileup) {
+        return Err(anyhow!(
+            "--depth-unit fragment is only supported in fast mode"
+        ));
+    }
+    if weight == Weight::mapq && matches!(mode, Mode::pileup) {
+        return Err(anyhow!("--weight mapq is only supported in fast mode"));
+    }
+    if usable_mapq.is_some() && matches!(mode, Mode::pileup) {
+        return Err(anyhow!("--usable-mapq is only supported in fast mode"));
+    }
+    // the view only counts the depth at the MAPQ it can step through
+    if let Some(mapq) = usable_mapq.filter(|mapq| !MAPQ_STEPS.contains(mapq)) {
+        return Err(anyhow!(
+            "--usable-mapq {} is not a step of the view, use one of {}",
+            mapq,
+            MAPQ_STEPS.map(|step| step.to_string()).join(", ")
+        ));
+    }
+    if variant_hints && matches!(mode, Mode::fast) {
+        return Err(anyhow!("--variant-hints is only supported in pileup mode"));
+    }
+
+    // index the inputs before any reader needs it
+    if build_index {
+        for bam in &bams {
+            if build_missing_index(Path::new(bam), threads)? {
+                log::info!("indexed {}", bam);
+            }
+        }
+    }
+
+    // one track per input file, or per sample of its read groups with --split-by-rg
+    let mut inputs = Vec::new();
+    for bam in &bams {
+        if !split_by_rg {
+            inputs.push(Input {
+                bam: bam.clone(),
+                name: file_name(bam),
+                read_groups: read_groups.clone(),
+            });
+            continue;
+        }
+        let header_reader = DepthProcessor::new(
+            PathBuf::from(bam),
+            reference.clone(),
+            AndFilter::new(),
+            false,
+        );
+        let samples = header_reader.read_group_samples()?;
+        if samples.is_empty() {
+            return Err(anyhow!(
+                "--split-by-rg: no read group in the header of {}",
+                bam
+            ));
+        }
+        for (sample, ids) in samples {
+            // several files may hold samples of the same name
+            let name = if bams.len() > 1 {
+                format!("{} {}", file_name(bam), sample)
+            } else {
+                sample
+            };
+            inputs.push(Input {
+                bam: bam.clone(),
+                name,
+                read_groups: ids,
+            });
+        }
+    }
+    if split_by_rg && normalize != Normalize::none {
+        return Err(anyhow!(
+            "--normalize counts the mapped reads of whole files, so it can't scale read groups"
+        ));
+    }
+
+    // create read filter, the strand is told apart by the reverse flag;
+    // `live` filters switched in the view replace the duplicate, proper pair and mapq ones
+    let make_filter = |include_flags: u16,
+                       exclude_flags: u16,
+                       read_groups: &[String],
+                       live: Option<LiveFilters>|
+     -> AndFilter {
+        let (exclude_flags, min_mapq, proper_pairs_only) = match live {
+            Some(live) => (
+                if live.dups {
+                    exclude_flags | DUPLICATE
+                } else {
+                    exclude_flags & !DUPLICATE
+                },
+                if live.mapq { min_mapq } else { 0 },
+                live.proper_pairs,
+            ),
+            None => (exclude_flags, min_mapq, proper_pairs_only),
+        };
+        // stack only the filters that were asked for
+        let mut read_filter = AndFilter::new().and(DefaultReadFilter::new(
+            include_flags,
+            exclude_flags,
+            min_mapq,
+        ));
+        if min_insert.is_some() || max_insert.is_some() {
+            read_filter = read_filter.and(InsertSizeFilter::new(min_insert, max_insert));
+        }
+        if proper_pairs_only {
+            read_filter = read_filter.and(ProperPairFilter);
+        }
+        if let Some(max_nm) = max_nm {
+            read_filter = read_filter.and(EditDistanceFilter::new(max_nm, !drop_missing_nm));
+        }
+        if min_read_len.is_some() || max_read_len.is_some() {
+            read_filter = read_filter.and(ReadLengthFilter::new(min_read_len, max_read_len));
+        }
+        if !read_groups.is_empty() {
+            read_filter = read_filter.and(ReadGroupFilter::new(read_groups.to_vec()));
+        }
+        read_filter
+    };
+    // the strands are told apart and the side tracks counted in the pass over the reads that
+    // counts the depth
+    let tracks = Tracks {
+        reverse: matches!(strand, Some(Strand::both)),
+        mapq: mapq_track,
+        softclips: softclip_track,
+        hints: variant_hints,
+        usable: usable_mapq.is_some(),
+    };
+    // create depth processor
+    // with --cache-dir, each processor saves its depths keyed by everything they depend on
+    let settings = format!(
+        "{:?} {:?} {:?} {} {} {:?} {:?} {} {:?} {} {:?} {:?} {} {}",
+        mode,
+        depth_unit,
+        weight,
+        min_mapq,
+        min_baseq,
+        min_insert,
+        max_insert,
+        proper_pairs_only,
+        max_nm,
+        drop_missing_nm,
+        min_read_len,
+        max_read_len,
+        del_as_gap,
+        count_supplementary
+    );
+    let with_cache = |processor: Box<dyn RegionProcessor + Sync>,
+                      input: &Input,
+                      kind: &str,
+                      include_flags: u16,
+                      exclude_flags: u16|
+     -> Box<dyn RegionProcessor + Sync> {
+        match &cache_dir {
+            // a URL has no modification time to tell when the cached depths are stale
+            Some(dir) if remote_url(Path::new(&input.bam)).is_none() => {
+                Box::new(CachedProcessor::new(
+                    processor,
+                    PathBuf::from(&input.bam),
+                    dir.clone(),
+                    format!(
+                        "{} {} {} {:?} {}",
+                        kind, include_flags, exclude_flags, input.read_groups, settings
+                    ),
+                ))
+            }
+            _ => processor,
+        }
+    };
+    // with `live` filters from the view, the depths are neither cached nor shown on the bar
+    let make_processor = |input: &Input,
+                          include_flags: u16,
+                          exclude_flags: u16,
+                          live: Option<LiveFilters>|
+     -> Box<dyn RegionProcessor + Sync> {
+        let bam_path = PathBuf::from(&input.bam);
+        let read_filter = make_filter(include_flags, exclude_flags, &input.read_groups, live);
+        let progress = if live.is_none() {
+            progress.clone()
+        } else {
+            None
+        };
+        let processor: Box<dyn RegionProcessor + Sync> = match mode {
+            Mode::fast => {
+                let mut processor = DepthProcessor::new(
+                    bam_path.clone(),
+                    reference.clone(),
+                    read_filter,
+                    del_as_gap,
+                );
+                processor.count_supplementary = count_supplementary;
+                processor.count_fragments = depth_unit == DepthUnit::fragment;
+                processor.weight_by_mapq = weight == Weight::mapq;
+                processor.tracks = tracks;
+                processor.progress = progress;
+                Box::new(processor)
+            }
+            Mode::pileup => {
+                let mut processor = PileupDepthProcessor::new(
+                    bam_path.clone(),
+                    reference.clone(),
+                    read_filter,
+                    min_baseq,
+                );
+                processor.count_supplementary = count_supplementary;
+                processor.tracks = tracks;
+                processor.progress = progress;
+                Box::new(processor)
+            }
+        };
+        if live.is_some() {
+            return processor;
+        }
+        let kind = format!("depth {:?}", tracks);
+        with_cache(processor, input, &kind, include_flags, exclude_flags)
+    };
+    // include and exclude flags of the depth track, both strands are counted with --strand both
+    let depth_flags = match strand {
+        Some(Strand::forward) => (include_flags, exclude_flags | REVERSE),
+        Some(Strand::reverse) => (include_flags | REVERSE, exclude_flags),
+        None | Some(Strand::both) => (include_flags, exclude_flags),
+    };
+    // one processor per track
+    let processors: Vec<_> = inputs
+        .iter()
+        .map(|input| make_processor(input, depth_flags.0, depth_flags.1, None))
+        .collect();
+
+    // the depth at one base of each track, both strands summed with --strand both
+    if let Some(Command::Query { position }) = &command {
+        if processors.is_empty() {
+            return Err(anyhow!("query needs --bam before it"));
+        }
+        let (mut chrom, pos) = parse_position(position, coord_base)?;
+        if normalize_contig {
+            chrom = resolve_contig(processors[0].as_ref(), &inputs[0].bam, &chrom)?;
+        }
+        let contig_len = processors[0].contig_len(&chrom)?;
+        if pos >= contig_len {
+            return Err(anyhow!(
+                "position {} is past the end of {}, {} bp long",
+                position,
+                chrom,
+                contig_len
+            ));
+        }
+        let mut out = io::stdout().lock();
+        for (input, processor) in inputs.iter().zip(&processors) {
+            let context = || region_context(&input.bam, &chrom, pos, pos + 1, coord_base);
+            let res = processor
+                .process_region(&chrom, pos, pos + 1)
+                .with_context(context)?;
+            // no read over the base is a depth of zero
+            let depth = res.depths.first().map_or(0, |x| x.depth)
+                + res
+                    .reverse
+                    .as_ref()
+                    .and_then(|reverse| reverse.first())
+                    .map_or(0, |depth| *depth);
+            if processors.len() > 1 {
+                writeln!(out, "{}\t{}", input.name, depth)?;
+            } else {
+                writeln!(out, "{}", depth)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // a read group filter that matches nothing in the header would only draw zeros
+    if !read_groups.is_empty() {
+        for (input, depth_processer) in inputs.iter().zip(&processors) {
+            let available = depth_processer.read_groups()?;
+            if !read_groups.iter().any(|rg| available.contains(rg)) {
+                return Err(anyhow!(
+                    "none of the read groups {} is in {}, available: {}",
+                    read_groups.join(", "),
+                    input.bam,
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                ));
+            }
+        }
+    }
+
+    // normalization factor of each input file, from the mapped reads in its index
+    let factors = processors
+        .iter()
+        .map(|depth_processer| match normalize {
+            Normalize::none => Ok(1.0),
+            _ => Ok(normalize.factor(depth_processer.mapped_reads()?)),
+        })
+        .collect::<Result<Vec<f64>>>()?;
+
+    // collect regions from cli and bed file
+    let mut targets = Vec::new();
+    for region in regions {
+        // loci streamed by a wrapper, read up to EOF so one process serves them all
+        if region == "-" {
+            targets.extend(read_regions(io::stdin().lock(), coord_base)?);
+            continue;
+        }
+        // a gene name, spanned from the annotation
+        if let Some(gene) = region.strip_prefix("gene:") {
+            let annotation = annotation
+                .as_ref()
+                .ok_or_else(|| anyhow!("--region {} needs --annotation", region))?;
+            targets.push(find_feature(annotation, gene)?);
+            continue;
+        }
+        let (chrom, start, end) = parse_region(&region, coord_base)?;
+        targets.push(Target {
+            chrom,
+            start,
+            end,
+            name: None,
+        });
+    }
+    if let Some(bed) = bed {
+        targets.extend(parse_bed(&bed)?);
+    }
+
+    // `chr2` asked of a file naming it `2`, or the other way round, looked up once per name
+    if normalize_contig {
+        let mut resolved: HashMap<String, String> = HashMap::new();
+        for target in targets.iter_mut() {
+            if !resolved.contains_key(&target.chrom) {
+                let chrom = resolve_contig(processors[0].as_ref(), &inputs[0].bam, &target.chrom)?;
+                resolved.insert(target.chrom.clone(), chrom);
+            }
+            target.chrom = resolved[&target.chrom].clone();
+        }
+    }
+
+    // context around each region, a whole contig has none to add
+    if pad > 0 {
+        for target in targets.iter_mut() {
+            target.start = target.start.saturating_sub(pad);
+            if target.end != u32::MAX {
+                let contig_len = processors[0].contig_len(&target.chrom)?;
+                target.end = target.end.saturating_add(pad).min(contig_len);
+            }
+        }
+    }
+
+    // an empty region has no column to draw, padding may have widened it
+    if let Some(target) = targets.iter().find(|target| target.start >= target.end) {
+        return Err(anyhow!(
+            "region {}:{}-{} is empty, the end must be past the start",
+            target.chrom,
+            target.start + coord_base,
+            target.end
+        ));
+    }
+
+    // nothing to show, e.g. stdin closed before any region
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    if screenshot.is_some() && targets.len() != 1 {
+        return Err(anyhow!("--screenshot needs exactly one region"));
+    }
+
+    // what would be counted, checked against the headers but without reading any alignment
+    if dry_run {
+        let mut out = io::stdout().lock();
+        writeln!(out, "coord_base={}", coord_base)?;
+        writeln!(out, "mode={:?}", mode)?;
+        writeln!(out, "depth_unit={:?}", depth_unit)?;
+        writeln!(out, "weight={:?}", weight)?;
+        for input in &inputs {
+            writeln!(out, "input={}", input.bam)?;
+            if !input.read_groups.is_empty() {
+                writeln!(out, "read_groups={}", input.read_groups.join(","))?;
+            }
+        }
+        for target in targets.iter_mut() {
+            target.clamp_to_contig(processors[0].contig_len(&target.chrom)?, coord_base)?;
+            writeln!(
+                out,
+                "region={}:{}-{}",
+                target.chrom,
+                target.start + coord_base,
+                target.end
+            )?;
+        }
+        // a read is counted when it has every include flag and none of the exclude flags,
+        // the strand is picked with the reverse flag
+        let tracks = match strand {
+            None => vec![("", include_flags, exclude_flags)],
+            Some(Strand::forward) => vec![("", include_flags, exclude_flags | REVERSE)],
+            Some(Strand::reverse) => vec![("", include_flags | REVERSE, exclude_flags)],
+            Some(Strand::both) => vec![
+                ("forward_", include_flags, exclude_flags | REVERSE),
+                ("reverse_", include_flags | REVERSE, exclude_flags),
+            ],
+        };
+        for (prefix, include_flags, exclude_flags) in tracks {
+            writeln!(
+                out,
+                "{}include_flags={:#x} {}",
+                prefix,
+                include_flags,
+                flag_names(include_flags)
+            )?;
+            writeln!(
+                out,
+                "{}exclude_flags={:#x} {}",
+                prefix,
+                exclude_flags,
+                flag_names(exclude_flags)
+            )?;
+        }
+        writeln!(out, "min_mapq={}", min_mapq)?;
+        if matches!(mode, Mode::pileup) {
+            writeln!(out, "min_baseq={}", min_baseq)?;
+        }
+        writeln!(out, "count_supplementary={}", count_supplementary)?;
+        writeln!(out, "del_as_gap={}", del_as_gap)?;
+        if let Some(min_insert) = min_insert {
+            writeln!(out, "min_insert={}", min_insert)?;
+        }
+        if let Some(max_insert) = max_insert {
+            writeln!(out, "max_insert={}", max_insert)?;
+        }
+        if proper_pairs_only {
+            writeln!(out, "proper_pairs_only=true")?;
+        }
+        if let Some(max_nm) = max_nm {
+            writeln!(out, "max_nm={}", max_nm)?;
+            writeln!(out, "drop_missing_nm={}", drop_missing_nm)?;
+        }
+        if let Some(min_read_len) = min_read_len {
+            writeln!(out, "min_read_len={}", min_read_len)?;
+        }
+        if let Some(max_read_len) = max_read_len {
+            writeln!(out, "max_read_len={}", max_read_len)?;
+        }
+        return Ok(());
+    }
+
+    // without tui, write depths to the output file or stdout
+    let mut writer: Option<Box<dyn Write>> = match output {
+        Some(path) if path == "-" => Some(Box::new(BufWriter::new(io::stdout().lock()))),
+        Some(path) => Some(Box::new(BufWriter::new(File::create(path)?))),
+        None if no_tui => Some(Box::new(BufWriter::new(io::stdout().lock()))),
+        None => None,
+    };
+    if writer.is_some() && tracks.reverse {
+        return Err(anyhow!(
+            "--strand both is only supported in the tui, use forward or reverse"
+        ));
+    }
+    if writer.is_none() && fail_under.is_some() {
+        return Err(anyhow!("--fail-under needs --output or --no-tui"));
+    }
+    if writer.is_some() && inputs.len() > 1 {
+        return Err(anyhow!(
+            "several tracks are only supported in the tui, write them one at a time"
+        ));
+    }
+
+    // bedgraph and tsv are streamed region by region, holding only the reads under the sweep,
+    // so a whole chromosome can be written without its depth array in memory
+    if let Some(writer) = writer.as_mut() {
+        if screenshot.is_none() && screenshot_dir.is_none() && !matches!(format, Format::json) {
+            let depth_processer = &processors[0];
+            // bases covered deep enough for --fail-under, and all bases written
+            let (mut covered, mut total) = (0, 0);
+            for mut target in targets {
+                target.clamp_to_contig(depth_processer.contig_len(&target.chrom)?, coord_base)?;
+                total += u64::from(target.end - target.start);
+                let chrom = &target.chrom;
+                let context =
+                    || region_context(&inputs[0].bam, chrom, target.start, target.end, coord_base);
+                // the sweep counts no reads, but without any a region has no depth either
+                let mut covered_any = false;
+                depth_processer
+                    .stream_region(chrom, target.start, target.end, &mut |start, end, depth| {
+                        covered_any |= depth > 0;
+                        if fail_under.is_some_and(|fail_under| depth >= fail_under.depth) {
+                            covered += u64::from(end - start);
+                        }
+                        match format {
+                            Format::tsv => {
+                                output::write_tsv_range(writer, chrom, start, end, depth)
+                            }
+                            _ => output::write_bedgraph_range(writer, chrom, start, end, depth),
+                        }
+                    })
+                    .with_context(context)?;
+                if !covered_any && !inputs[0].read_groups.is_empty() {
+                    warn_no_reads(&inputs[0], &target, coord_base);
+                }
+            }
+            if let Some(bar) = &progress {
+                bar.finish_and_clear();
+            }
+            writer.flush()?;
+            if let Some(fail_under) = fail_under {
+                check_breadth(fail_under, covered, total);
+            }
+            return Ok(());
+        }
+    }
+
+    // the view only draws bins, so they are merged as the depths are summed; the plots and the
+    // written depths need every position
+    let bin_at_source = writer.is_none() && screenshot.is_none() && screenshot_dir.is_none();
+
+    // process regions in parallel, each with its own reader, keeping the input order
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+    let results = pool.install(|| {
+        targets
+            .into_par_iter()
+            .map(|mut target| {
+                // a bare contig means the whole contig and no region goes past its end,
+                // taken from the first file
+                target.clamp_to_contig(processors[0].contig_len(&target.chrom)?, coord_base)?;
+                // very large regions are binned down so the view stays responsive
+                let binning = Binning {
+                    size: match bin_at_source {
+                        true => bin_size
+                            .unwrap_or_else(|| tui::auto_bin_size(target.end - target.start)),
+                        false => 1,
+                    },
+                    stat: bin_stat.into(),
+                    low_depth: min_cov,
+                };
+                let samples = processors
+                    .iter()
+                    .zip(&inputs)
+                    .map(|(depth_processer, input)| {
+                        let context = || {
+                            region_context(
+                                &input.bam,
+                                &target.chrom,
+                                target.start,
+                                target.end,
+                                coord_base,
+                            )
+                        };
+                        depth_processer
+                            .process_binned(&target.chrom, target.start, target.end, binning)
+                            .with_context(context)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((target, samples))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+    if let Some(bar) = &progress {
+        bar.finish_and_clear();
+    }
+    for (target, samples) in &results {
+        for (input, counted) in inputs.iter().zip(samples) {
+            if counted.reads == 0 {
+                warn_no_reads(input, target, coord_base);
+            }
+        }
+    }
+
+    // plot the only region, or each region into the directory, instead of viewing them
+    let screenshots: Vec<(PathBuf, usize)> = match (screenshot, &screenshot_dir) {
+        (Some(path), _) => vec![(PathBuf::from(path), 0)],
+        (None, Some(dir)) => {
+            std::fs::create_dir_all(dir)?;
+            let mut names = HashSet::new();
+            results
+                .iter()
+                .enumerate()
+                .map(|(i, (target, _))| {
+                    let mut name = screenshot_name(target, coord_base, true);
+                    // regions sharing a BED name, such as the exons of a gene, keep their own file
+                    if !names.insert(name.clone()) {
+                        name = screenshot_name(target, coord_base, false);
+                    }
+                    (Path::new(dir).join(format!("{}.png", name)), i)
+                })
+                .collect()
+        }
+        (None, None) => Vec::new(),
+    };
+    if !screenshots.is_empty() {
+        let colors = tui::track_colors(color, inputs.len());
+        let reverse_color = reverse_color.to_ratatui_color();
+        pool.install(|| {
+            screenshots
+                .par_iter()
+                .try_for_each(|(path, i)| -> Result<()> {
+                    let (target, samples) = &results[*i];
+                    let plot_samples: Vec<PlotSample> = inputs
+                        .iter()
+                        .zip(samples)
+                        .zip(&colors)
+                        .map(|((input, counted), &color)| PlotSample {
+                            name: &input.name,
+                            forward: &counted.depths,
+                            reverse: counted.reverse.as_deref(),
+                            color,
+                            reverse_color,
+                        })
+                        .collect();
+                    let title = target.name.clone().unwrap_or_else(|| {
+                        format!(
+                            "{}:{}-{}",
+                            target.chrom,
+                            target.start + coord_base,
+                            target.end
+                        )
+                    });
+                    plot::write_png(
+                        path,
+                        &title,
+                        target.start,
+                        target.end,
+                        &plot_samples,
+                        plot_size,
+                    )?;
+                    log::info!("saved {}", path.display());
+                    Ok(())
+                })
+        })?;
+        if writer.is_none() {
+            return Ok(());
+        }
+    }
+
+    // the reference is only read for the gc track and the N mask of the view
+    let view_reference = match (&reference, (gc || mask_n) && writer.is_none()) {
+        (Some(reference), true) => Some(Reference::from_path(reference)?),
+        _ => None,
+    };
+
+    // the depth track of one input file, its statistics cover both strands together
+    let sample_cov = |name: String, counted: RegionDepths, factor: f64| -> SampleCov {
+        let RegionDepths {
+            depths,
+            reads,
+            histogram,
+            low_runs,
+            reverse,
+            mapq,
+            softclips,
+            hints,
+            usable,
+            weighted,
+            ..
+        } = counted;
+        let data: Vec<u64> = depths.iter().map(|x| x.depth as u64).collect();
+        let mut sample = SampleCov::new(name, data).with_reads(reads);
+        if let Some(reverse) = reverse {
+            sample = sample.with_reverse(reverse.into_iter().map(u64::from).collect());
+        }
+        if let Some(mapq) = mapq {
+            sample = sample.with_mapq(mapq.into_iter().map(u64::from).collect());
+        }
+        if let Some(softclips) = softclips {
+            sample = sample.with_softclips(softclips.into_iter().map(u64::from).collect());
+        }
+        if let Some(hints) = hints {
+            sample = sample.with_hints(hints.into_iter().map(u64::from).collect());
+        }
+        if let Some(weighted) = weighted {
+            sample = sample.with_weighted(weighted);
+        }
+        if let Some(usable) = usable {
+            sample = sample.with_usable(
+                usable
+                    .into_iter()
+                    .map(|depths| depths.into_iter().map(u64::from).collect())
+                    .collect(),
+            );
+        }
+        sample = sample
+            .with_stats(histogram.stats())
+            .with_breadth(min_breadth_depth, histogram.breadth(min_breadth_depth))
+            .with_low_runs(low_runs);
+        if normalize != Normalize::none {
+            sample = sample.with_normalization(factor, normalize.unit());
+        }
+        sample
+    };
 
-    // parse region
-    let (chrom, start, end) = parse_region(region)?;
+    let mut covs = Vec::with_capacity(results.len());
+    let mut summaries = Vec::new();
+    let (mut covered, mut total) = (0, 0);
+    for (target, samples) in results {
+        if let Some(writer) = writer.as_mut() {
+            for RegionDepths { depths: res, .. } in &samples {
+                if let Some(fail_under) = fail_under {
+                    covered += covered_bases(res, fail_under.depth);
+                    total += res.len() as u64;
+                }
+                match format {
+                    Format::bedgraph => output::write_bedgraph(writer, res)?,
+                    Format::tsv => output::write_tsv(writer, res)?,
+                    Format::json => {
+                        let mut summary = CoverageSummary::new(
+                            target.chrom.clone(),
+                            target.start,
+                            target.end,
+                            target.name.clone(),
+                            &depth_stats(res),
+                        );
+                        if json_depths {
+                            let bin_size = bin_size.unwrap_or(1);
+                            let data: Vec<u64> = res.iter().map(|x| x.depth as u64).collect();
+                            let depths = tui::bin_depths(&data, bin_size as usize, bin_stat);
+                            summary = summary.with_depths(bin_size, depths);
+                        }
+                        summaries.push(summary);
+                    }
+                }
+            }
+            continue;
+        }
 
-    // create read filter and depth processor
-    let read_filter = DefaultReadFilter::new(include_flags, exclude_flags, min_mapq);
-    let bam_path = PathBuf::from(bam); // check it
-    let depth_processer = DepthProcessor::new(bam_path, read_filter);
-    let res = depth_processer.process_region(&chrom, start, end)?;
+        // get the depth data of each input file
+        let Target {
+            chrom,
+            start,
+            end,
+            name,
+        } = target;
+        let bin_size = samples.first().map_or(1, |counted| counted.bin_size);
+        let mut sample_covs = Vec::with_capacity(samples.len());
+        for ((input, factor), counted) in inputs.iter().zip(&factors).zip(samples) {
+            sample_covs.push(sample_cov(input.name.clone(), counted, *factor));
+        }
+        let seq = view_reference
+            .as_ref()
+            .map(|view_reference| view_reference.fetch(&chrom, start, end))
+            .transpose()?;
+        let variants = vcf
+            .as_ref()
+            .map(|vcf| fetch_variants(vcf, &chrom, start, end))
+            .transpose()?;
+        let features = annotation
+            .as_ref()
+            .map(|annotation| load_features(annotation, &chrom, start, end))
+            .transpose()?;
+        let expected = baseline
+            .as_ref()
+            .map(|baseline| load_baseline(baseline, &chrom, start, end))
+            .transpose()?;
+        let legend = name.unwrap_or_else(|| format!("{}:{}-{}", chrom, start + coord_base, end));
+        let mut cov = RegionCov::new(chrom, start, end, legend, sample_covs);
+        if let Some(expected) = expected {
+            cov = cov.with_baseline(expected);
+        }
+        let mut cov = cov.with_bin_size(bin_size);
+        if let Some(seq) = seq {
+            if gc {
+                cov = cov.with_gc(gc_percent(&seq, bin_size as usize));
+            }
+            if mask_n {
+                cov = cov.with_n_percent(n_percent(&seq, bin_size as usize));
+            }
+        }
+        if let Some(variants) = variants {
+            cov = cov.with_variants(variants);
+        }
+        if let Some(features) = features {
+            cov = cov.with_features(features);
+        }
+        covs.push(cov);
+    }
+
+    if let Some(mut writer) = writer {
+        // the json array is only complete once every region is summarized
+        if let Format::json = format {
+            output::write_json(&mut writer, &summaries)?;
+        }
+        writer.flush()?;
+        if let Some(fail_under) = fail_under {
+            check_breadth(fail_under, covered, total);
+        }
+        return Ok(());
+    }
 
-    // get the depth data
-    let data: Vec<u64> = res.iter().map(|x| x.depth as u64).collect();
-    let legend = format!("{}:{}-{}", chrom, start, end);
+    // the depth tracks of a region counted again with the filters switched in the view
+    let reload = |chrom: &str,
+                  start: u32,
+                  end: u32,
+                  size: u32,
+                  low_depth: Option<u32>,
+                  live: LiveFilters| {
+        let binning = Binning {
+            size,
+            stat: bin_stat.into(),
+            low_depth,
+        };
+        inputs
+            .iter()
+            .zip(&factors)
+            .map(|(input, &factor)| {
+                let context = || region_context(&input.bam, chrom, start, end, coord_base);
+                let counted = make_processor(input, depth_flags.0, depth_flags.1, Some(live))
+                    .process_binned(chrom, start, end, binning)
+                    .with_context(context)?;
+                Ok(sample_cov(input.name.clone(), counted, factor))
+            })
+            .collect::<Result<Vec<_>>>()
+    };
 
     // run tui
-    tui::tview(data, start, legend, step_size, color)
+    let options = ViewOptions {
+        step: step_size,
+        color,
+        reverse_color,
+        label_color,
+        colormap,
+        chart,
+        log,
+        bin_stat,
+        smooth,
+        stats,
+        min_cov,
+        flip,
+        max_depth,
+        hist_bins,
+        usable_mapq,
+        tick_ms,
+        redraw_on_event,
+        mouse,
+        filters: LiveFilters {
+            dups: exclude_flags & DUPLICATE != 0,
+            proper_pairs: proper_pairs_only,
+            mapq: min_mapq > 0,
+        },
+        min_mapq,
+        weighted: weight == Weight::mapq,
+        reload: &reload,
+        session,
+        coord_base,
+    };
+    tui::tview(covs, options)
 }
 
-/// Parse the region string into chrom, start, end; if invalid, return an error
-fn parse_region(region: String) -> Result<(String, u32, u32)> {
-    let parts: Vec<&str> = region.split(':').collect();
-    if parts.len() != 2 {
-        return Err(anyhow!("Invalid region format"));
+// print whether enough of the written bases are covered deep enough, exiting with 1 if not,
+// so a pipeline step fails on low coverage
+fn check_breadth(fail_under: FailUnder, covered: u64, total: u64) {
+    let fraction = if total == 0 {
+        0.0
+    } else {
+        covered as f64 / total as f64
+    };
+    let pass = fraction >= fail_under.fraction;
+    eprintln!(
+        "{}: {:.2}% of {} bases covered at least {}x, {:.2}% needed",
+        if pass { "PASS" } else { "FAIL" },
+        fraction * 100.0,
+        total,
+        fail_under.depth,
+        fail_under.fraction * 100.0
+    );
+    if !pass {
+        std::process::exit(1);
     }
-    let chrom = parts[0].to_string();
-    let start_end: Vec<&str> = parts[1].split('-').collect();
-    if start_end.len() != 2 {
-        return Err(anyhow!("Invalid region format"));
+}
+
+// names of the SAM flags set in `flags`, comma separated, `-` for none
+fn flag_names(flags: u16) -> String {
+    let names: Vec<&str> = FLAG_NAMES
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join(",")
+    }
+}
+
+// the file name is enough to tell tracks apart
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |x| x.to_string_lossy().into_owned())
+}
+
+// file name of a region under --screenshot-dir, its BED name if `by_name` and it has one or its
+// coordinates, with anything but letters, digits, `.`, `-` and `_` replaced
+fn screenshot_name(target: &Target, coord_base: u32, by_name: bool) -> String {
+    let name = match &target.name {
+        Some(name) if by_name => name.clone(),
+        _ => format!(
+            "{}_{}-{}",
+            target.chrom,
+            target.start + coord_base,
+            target.end
+        ),
+    };
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// warn that no read of `input` passed the filters over a region, which draws a flat zero
+// track; read groups that are declared but hold no read there are the likeliest cause
+fn warn_no_reads(input: &Input, target: &Target, coord_base: u32) {
+    let region = format!(
+        "{}:{}-{}",
+        target.chrom,
+        target.start + coord_base,
+        target.end
+    );
+    if input.read_groups.is_empty() {
+        log::warn!(
+            "no read of {} passed the filters over {}",
+            input.bam,
+            region
+        );
+    } else {
+        log::warn!(
+            "no read of read group {} in {} passed the filters over {}",
+            input.read_groups.join(", "),
+            input.bam,
+            region
+        );
+    }
+}
+
+// what was being read when an error came up, in the coordinates given on the command line
+fn region_context(bam: &str, chrom: &str, start: u32, end: u32, coord_base: u32) -> String {
+    format!(
+        "while counting {}:{}-{} of {}",
+        chrom,
+        start + coord_base,
+        end,
+        bam
+    )
+}
+
+// the name of `chrom` in the header of `bam`, warning when only its other `chr` form is there
+fn resolve_contig(
+    processor: &(dyn RegionProcessor + Sync),
+    bam: &str,
+    chrom: &str,
+) -> Result<String> {
+    let resolved = processor.resolve_contig(chrom)?;
+    if resolved != chrom {
+        log::warn!(
+            "contig {} is named {} in {}, using it",
+            chrom,
+            resolved,
+            bam
+        );
     }
-    let start = start_end[0].parse::<u32>()?;
-    let end = start_end[1].parse::<u32>()?;
-    Ok((chrom, start, end))
+    Ok(resolved)
 }
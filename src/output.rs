@@ -0,0 +1,53 @@
+use crate::cov::{merge_positions, BedGraph};
+use crate::stats::CoverageSummary;
+use anyhow::Result;
+use std::io::Write;
+
+/// Write the summaries as a pretty-printed JSON array
+pub fn write_json<W: Write>(writer: &mut W, summaries: &[CoverageSummary]) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, summaries)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write one `chrom\tpos\tdepth` line per position with 1-based positions, like `samtools depth`
+pub fn write_tsv<W: Write>(writer: &mut W, positions: &[BedGraph]) -> Result<()> {
+    for pos in positions {
+        writeln!(writer, "{}\t{}\t{}", pos.ref_seq, pos.pos + 1, pos.depth)?;
+    }
+    Ok(())
+}
+
+/// Write the positions of a 0-based half-open range of equal depth, one `write_tsv` line each
+pub fn write_tsv_range<W: Write>(
+    writer: &mut W,
+    chrom: &str,
+    start: u32,
+    end: u32,
+    depth: u32,
+) -> Result<()> {
+    for pos in start..end {
+        writeln!(writer, "{}\t{}\t{}", chrom, pos + 1, depth)?;
+    }
+    Ok(())
+}
+
+/// Write a 0-based half-open range of equal depth as one bedgraph line
+pub fn write_bedgraph_range<W: Write>(
+    writer: &mut W,
+    chrom: &str,
+    start: u32,
+    end: u32,
+    depth: u32,
+) -> Result<()> {
+    writeln!(writer, "{}\t{}\t{}\t{}", chrom, start, end, depth)?;
+    Ok(())
+}
+
+/// Write positions as bedgraph, merging consecutive positions with the same depth into one range
+pub fn write_bedgraph<W: Write>(writer: &mut W, positions: &[BedGraph]) -> Result<()> {
+    for range in merge_positions(positions) {
+        writeln!(writer, "{}", range)?;
+    }
+    Ok(())
+}
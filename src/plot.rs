@@ -0,0 +1,88 @@
+use crate::svg::rgb;
+use anyhow::Result;
+use plotters::prelude::*;
+// the plotters color trait, its name is taken by the terminal color
+use plotters::style::Color as _;
+use ratatui::style::Color;
+use std::path::Path;
+use tcov::cov::BedGraph;
+
+/// Coverage of one input file over the whole region, as plotted
+pub struct PlotSample<'a> {
+    pub name: &'a str,
+    /// depths of every position, the forward strand if `reverse` is set
+    pub forward: &'a [BedGraph],
+    /// depths of the reverse strand from the region start on, plotted below zero
+    pub reverse: Option<&'a [u32]>,
+    pub color: Color,
+    pub reverse_color: Color,
+}
+
+/// Draw the samples as filled area charts over the coordinates from `start` to `end` into a PNG
+pub fn write_png(
+    path: &Path,
+    title: &str,
+    start: u32,
+    end: u32,
+    samples: &[PlotSample],
+    size: (u32, u32),
+) -> Result<()> {
+    let max_of = |positions: &[BedGraph]| positions.iter().map(|x| x.depth).max().unwrap_or(0);
+    let max = samples
+        .iter()
+        .map(|s| max_of(s.forward))
+        .max()
+        .unwrap_or(0)
+        .max(1) as i64;
+    let min = -(samples
+        .iter()
+        .filter_map(|s| s.reverse.and_then(|reverse| reverse.iter().max().copied()))
+        .max()
+        .unwrap_or(0) as i64);
+
+    let root = BitMapBackend::new(path, size).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(start..end.max(start + 1), min..max)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_desc("position")
+        .y_desc("depth")
+        // the reverse strand counts down from zero, but its depths are positive
+        .y_label_formatter(&|y| y.abs().to_string())
+        .draw()?;
+
+    for sample in samples {
+        let (r, g, b) = rgb(sample.color);
+        let color = RGBColor(r, g, b);
+        let points = sample.forward.iter().map(|x| (x.pos, x.depth as i64));
+        chart
+            .draw_series(AreaSeries::new(points, 0, color.mix(0.4)).border_style(color))?
+            .label(sample.name)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+        if let Some(reverse) = sample.reverse {
+            let (r, g, b) = rgb(sample.reverse_color);
+            let reverse_color = RGBColor(r, g, b);
+            let points = (start..)
+                .zip(reverse)
+                .map(|(pos, &depth)| (pos, -(depth as i64)));
+            chart.draw_series(
+                AreaSeries::new(points, 0, reverse_color.mix(0.4)).border_style(reverse_color),
+            )?;
+        }
+    }
+    if samples.len() > 1 {
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// A bar on stderr for the bases of all regions processed before the tui opens or the output is
+/// written, `None` with `quiet`.
+///
+/// It starts empty, each region adds its length when it starts, so whole contigs don't need
+/// their lengths up front. Nothing is drawn when stderr isn't a terminal.
+pub fn progress_bar(quiet: bool) -> Option<ProgressBar> {
+    if quiet {
+        return None;
+    }
+    let bar = ProgressBar::new(0);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} {bar:40} {percent:>3}% of {human_len} bases ({eta})",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> "),
+    );
+    Some(bar)
+}
+
+/// Progress of one region on a shared bar, advanced by the position of the reads consumed
+///
+/// Reads come sorted by start, so the position of the last one estimates how much of the region
+/// is done; whatever is left is counted when the region is dropped, finished or not.
+pub struct RegionProgress<'a> {
+    bar: Option<&'a ProgressBar>,
+    /// position the bar has been advanced to
+    pos: u32,
+    stop: u32,
+}
+
+impl<'a> RegionProgress<'a> {
+    /// Add the region from `start` to exclusive `stop` to the bar
+    pub fn new(bar: Option<&'a ProgressBar>, start: u32, stop: u32) -> Self {
+        if let Some(bar) = bar {
+            bar.inc_length(u64::from(stop.saturating_sub(start)));
+        }
+        Self {
+            bar,
+            pos: start.min(stop),
+            stop,
+        }
+    }
+
+    /// Count the bases up to `pos`, earlier positions than the last one are ignored
+    pub fn advance_to(&mut self, pos: i64) {
+        let Some(bar) = self.bar else {
+            return;
+        };
+        let pos = pos.clamp(i64::from(self.pos), i64::from(self.stop)) as u32;
+        bar.inc(u64::from(pos - self.pos));
+        self.pos = pos;
+    }
+}
+
+impl Drop for RegionProgress<'_> {
+    fn drop(&mut self) {
+        self.advance_to(i64::from(self.stop));
+    }
+}
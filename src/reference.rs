@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+use rust_htslib::faidx;
+use std::path::Path;
+
+/// Reference fasta read through its `.fai` index, built by htslib if missing
+pub struct Reference {
+    reader: faidx::Reader,
+    /// contig names of the index, checked before fetching
+    names: Vec<String>,
+}
+
+impl Reference {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = faidx::Reader::from_path(path)
+            .map_err(|e| anyhow!("failed to open reference {}: {}", path.display(), e))?;
+        let names = reader.seq_names()?;
+        Ok(Self { reader, names })
+    }
+
+    /// Bases from 0-based `start` to exclusive `end` of `chrom`, upper-cased
+    pub fn fetch(&self, chrom: &str, start: u32, end: u32) -> Result<Vec<u8>> {
+        // htslib hands back a null sequence for unknown contigs
+        if !self.names.iter().any(|name| name == chrom) {
+            return Err(anyhow!("Contig {} not found in the reference", chrom));
+        }
+        if end <= start {
+            return Ok(Vec::new());
+        }
+        let seq = self
+            .reader
+            .fetch_seq(chrom, start as usize, end as usize - 1)?;
+        Ok(seq.to_ascii_uppercase())
+    }
+}
+
+/// Percentage of N bases in every `bin_size` bases, gaps of the assembly or masked repeats
+pub fn n_percent(seq: &[u8], bin_size: usize) -> Vec<u64> {
+    seq.chunks(bin_size.max(1))
+        .map(|bin| {
+            let n = bin.iter().filter(|&&b| b == b'N').count() as u64;
+            n * 100 / bin.len() as u64
+        })
+        .collect()
+}
+
+/// GC percentage of every `bin_size` bases, N and other ambiguous bases left out.
+///
+/// A bin without any A, C, G or T is 0.
+pub fn gc_percent(seq: &[u8], bin_size: usize) -> Vec<u64> {
+    seq.chunks(bin_size.max(1))
+        .map(|bin| {
+            let gc = bin.iter().filter(|&&b| matches!(b, b'G' | b'C')).count() as u64;
+            let at = bin.iter().filter(|&&b| matches!(b, b'A' | b'T')).count() as u64;
+            (gc * 100).checked_div(gc + at).unwrap_or(0)
+        })
+        .collect()
+}
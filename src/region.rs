@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// A region to view, with an optional name used as legend
+pub struct Target {
+    /// contig name
+    pub chrom: String,
+    /// 0-based start
+    pub start: u32,
+    /// end, [`u32::MAX`] for the whole contig
+    pub end: u32,
+    /// optional name, e.g. from the BED name column
+    pub name: Option<String>,
+}
+
+impl Target {
+    /// End the target at the end of its contig, `contig_len` bases long.
+    ///
+    /// htslib stops fetching there anyway, so an end past it would only add zeros; a bare
+    /// contig ends there quietly, a given end past it is warned about. Starting at or past the
+    /// contig end leaves nothing to show and is an error. Messages count from `coord_base` as
+    /// the region was given.
+    pub fn clamp_to_contig(&mut self, contig_len: u32, coord_base: u32) -> Result<()> {
+        if self.end != u32::MAX && self.end > contig_len {
+            log::warn!(
+                "{}:{}-{} ends past the contig end, clamped to {}",
+                self.chrom,
+                self.start + coord_base,
+                self.end,
+                contig_len
+            );
+        }
+        self.end = self.end.min(contig_len);
+        if self.start >= self.end {
+            return Err(anyhow!(
+                "region {}:{}-{} starts past the end of the contig, {} bp long",
+                self.chrom,
+                self.start + coord_base,
+                self.end,
+                contig_len
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parse the region string into chrom, 0-based start, exclusive end; if invalid, return an error.
+///
+/// Accepts `chr:start-end` with optional thousands separators (`chr2:2,078,887-2,079,669`),
+/// or a bare `chr` meaning the whole contig, in which case end is [`u32::MAX`]. With a
+/// `coord_base` of 1 the coordinates are 1-based inclusive like samtools, so `chr:1-100` is
+/// the first 100 bases; with 0 they are 0-based half-open like BED, `chr:0-100`.
+pub fn parse_region(region: &str, coord_base: u32) -> Result<(String, u32, u32)> {
+    let region = region.trim();
+    let (chrom, coords) = match region.rsplit_once(':') {
+        Some((chrom, coords)) => (chrom, Some(coords)),
+        None => (region, None),
+    };
+    if chrom.is_empty() {
+        return Err(anyhow!("Invalid region `{}`: missing contig name", region));
+    }
+    let coords = match coords {
+        Some(coords) => coords,
+        None => return Ok((chrom.to_string(), 0, u32::MAX)),
+    };
+    let (start, end) = coords
+        .split_once('-')
+        .ok_or_else(|| anyhow!("Invalid region `{}`: expected format chr:start-end", region))?;
+    let parse_coord = |coord: &str| {
+        coord.replace(',', "").parse::<u32>().map_err(|e| {
+            anyhow!(
+                "Invalid region `{}`: bad coordinate `{}` ({})",
+                region,
+                coord,
+                e
+            )
+        })
+    };
+    let start = parse_coord(start)?;
+    let end = parse_coord(end)?;
+    if start > end {
+        return Err(anyhow!(
+            "Invalid region `{}`: start {} is greater than end {}",
+            region,
+            start,
+            end
+        ));
+    }
+    if start < coord_base {
+        return Err(anyhow!(
+            "Invalid region `{}`: positions count from {}, see --coord-base",
+            region,
+            coord_base
+        ));
+    }
+    // an inclusive 1-based end is already the exclusive 0-based one
+    Ok((chrom.to_string(), start - coord_base, end))
+}
+
+/// Parse a `chr:pos` position, counted from `coord_base` as in [parse_region], into chrom and
+/// 0-based position.
+pub fn parse_position(position: &str, coord_base: u32) -> Result<(String, u32)> {
+    let position = position.trim();
+    let (chrom, pos) = position
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("Invalid position `{}`: expected format chr:pos", position))?;
+    if chrom.is_empty() {
+        return Err(anyhow!(
+            "Invalid position `{}`: missing contig name",
+            position
+        ));
+    }
+    let pos = pos.replace(',', "").parse::<u32>().map_err(|e| {
+        anyhow!(
+            "Invalid position `{}`: bad coordinate `{}` ({})",
+            position,
+            pos,
+            e
+        )
+    })?;
+    if pos < coord_base {
+        return Err(anyhow!(
+            "Invalid position `{}`: positions count from {}, see --coord-base",
+            position,
+            coord_base
+        ));
+    }
+    Ok((chrom.to_string(), pos - coord_base))
+}
+
+/// Read one region per line until EOF, e.g. from stdin, counted from `coord_base` as in
+/// [parse_region].
+///
+/// Blank and comment lines are skipped; errors report the line number.
+pub fn read_regions<R: BufRead>(reader: R, coord_base: u32) -> Result<Vec<Target>> {
+    let mut regions = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (chrom, start, end) =
+            parse_region(line, coord_base).map_err(|e| anyhow!("stdin:{}: {}", i + 1, e))?;
+        regions.push(Target {
+            chrom,
+            start,
+            end,
+            name: None,
+        });
+    }
+    Ok(regions)
+}
+
+/// Parse regions from a BED file.
+///
+/// Blank, comment, `track` and `browser` lines are skipped; errors report the line number.
+pub fn parse_bed(path: &str) -> Result<Vec<Target>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut regions = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = i + 1;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(anyhow!(
+                "{}:{}: expected at least 3 columns, found {}",
+                path,
+                line_no,
+                fields.len()
+            ));
+        }
+        let parse_coord = |coord: &str| {
+            coord
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| anyhow!("{}:{}: bad coordinate `{}` ({})", path, line_no, coord, e))
+        };
+        let start = parse_coord(fields[1])?;
+        let end = parse_coord(fields[2])?;
+        if start > end {
+            return Err(anyhow!(
+                "{}:{}: start {} is greater than end {}",
+                path,
+                line_no,
+                start,
+                end
+            ));
+        }
+        let name = fields
+            .get(3)
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(String::from);
+        regions.push(Target {
+            chrom: fields[0].to_string(),
+            start,
+            end,
+            name,
+        });
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_region_counts_from_coord_base() {
+        // one base, 1-based inclusive
+        assert_eq!(
+            parse_region("2:100-100", 1).unwrap(),
+            ("2".to_string(), 99, 100)
+        );
+        // an empty 0-based half-open region, left for the caller to reject
+        assert_eq!(
+            parse_region("2:100-100", 0).unwrap(),
+            ("2".to_string(), 100, 100)
+        );
+        assert_eq!(
+            parse_region("2:100-99", 1).unwrap_err().to_string(),
+            "Invalid region `2:100-99`: start 100 is greater than end 99"
+        );
+    }
+}
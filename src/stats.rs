@@ -0,0 +1,245 @@
+use crate::cov::BedGraph;
+use serde::Serialize;
+
+/// Depths reported as the fraction of positions covered at least this deep
+pub const THRESHOLDS: [u32; 3] = [1, 10, 30];
+
+/// Summary of the depths over a region
+#[derive(Debug, Clone, Default)]
+pub struct DepthStats {
+    pub mean: f64,
+    pub median: f64,
+    pub min: u32,
+    pub max: u32,
+    /// population standard deviation
+    pub std_dev: f64,
+    /// fraction of positions at or above each of [THRESHOLDS]
+    pub breadth: [f64; THRESHOLDS.len()],
+}
+
+/// Summarize the depths of positions, all zero for an empty slice
+pub fn depth_stats(positions: &[BedGraph]) -> DepthStats {
+    if positions.is_empty() {
+        return DepthStats::default();
+    }
+    let mut depths: Vec<u32> = positions.iter().map(|x| x.depth).collect();
+    depths.sort_unstable();
+    let n = depths.len();
+
+    let sum: u64 = depths.iter().map(|&d| d as u64).sum();
+    let mean = sum as f64 / n as f64;
+    let variance = depths
+        .iter()
+        .map(|&d| (d as f64 - mean).powi(2))
+        .sum::<f64>()
+        / n as f64;
+    let median = if n.is_multiple_of(2) {
+        (depths[n / 2 - 1] as f64 + depths[n / 2] as f64) / 2.0
+    } else {
+        depths[n / 2] as f64
+    };
+
+    // depths are sorted, so the positions below a threshold form a prefix
+    let breadth = THRESHOLDS.map(|t| (n - depths.partition_point(|&d| d < t)) as f64 / n as f64);
+
+    DepthStats {
+        mean,
+        median,
+        min: depths[0],
+        max: depths[n - 1],
+        std_dev: variance.sqrt(),
+        breadth,
+    }
+}
+
+/// Depths of a region tallied as the number of positions at each depth, so they can be
+/// summarized like [depth_stats] without keeping one depth per position
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepthHistogram {
+    /// positions at each depth, indexed by depth
+    counts: Vec<u64>,
+}
+
+impl DepthHistogram {
+    /// Tally a histogram from the number of positions at each depth
+    pub fn from_counts(counts: Vec<u64>) -> Self {
+        Self { counts }
+    }
+
+    /// Number of positions at each depth, indexed by depth
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Tally one position
+    pub fn add(&mut self, depth: u32) {
+        let depth = depth as usize;
+        if depth >= self.counts.len() {
+            self.counts.resize(depth + 1, 0);
+        }
+        self.counts[depth] += 1;
+    }
+
+    /// Number of positions tallied
+    pub fn len(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Whether no position was tallied
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // depth of the position at `rank` once sorted, counted from 0
+    fn nth(&self, rank: u64) -> u32 {
+        let mut seen = 0;
+        for (depth, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen > rank {
+                return depth as u32;
+            }
+        }
+        0
+    }
+
+    /// Summarize the tallied depths, as [depth_stats] summarizes positions
+    pub fn stats(&self) -> DepthStats {
+        let n = self.len();
+        if n == 0 {
+            return DepthStats::default();
+        }
+        let depths = || {
+            self.counts
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+        };
+        let sum: f64 = depths().map(|(d, &count)| d as f64 * count as f64).sum();
+        let mean = sum / n as f64;
+        let variance = depths()
+            .map(|(d, &count)| (d as f64 - mean).powi(2) * count as f64)
+            .sum::<f64>()
+            / n as f64;
+        let median = if n.is_multiple_of(2) {
+            (self.nth(n / 2 - 1) as f64 + self.nth(n / 2) as f64) / 2.0
+        } else {
+            self.nth(n / 2) as f64
+        };
+        DepthStats {
+            mean,
+            median,
+            min: depths().next().map_or(0, |(d, _)| d as u32),
+            max: depths().next_back().map_or(0, |(d, _)| d as u32),
+            std_dev: variance.sqrt(),
+            breadth: THRESHOLDS.map(|t| self.breadth(t)),
+        }
+    }
+
+    /// Number of positions covered at least `min_depth` deep
+    pub fn covered_bases(&self, min_depth: u32) -> u64 {
+        self.counts.iter().skip(min_depth as usize).sum()
+    }
+
+    /// Fraction of positions covered at least `min_depth` deep, 0 if none was tallied
+    pub fn breadth(&self, min_depth: u32) -> f64 {
+        match self.len() {
+            0 => 0.0,
+            n => self.covered_bases(min_depth) as f64 / n as f64,
+        }
+    }
+}
+
+/// Number of positions covered at least `min_depth` deep
+pub fn covered_bases(positions: &[BedGraph], min_depth: u32) -> u64 {
+    positions.iter().filter(|x| x.depth >= min_depth).count() as u64
+}
+
+/// Summary of the coverage of one region, as written by `--format json`.
+///
+/// Each region becomes one object of the written array, with this stable layout:
+///
+/// ```json
+/// {
+///   "chrom": "2",
+///   "start": 2078887,
+///   "end": 2079669,
+///   "name": null,
+///   "mean": 44.0,
+///   "median": 34.0,
+///   "min": 0,
+///   "max": 137,
+///   "std_dev": 44.9,
+///   "breadth": [{ "min_depth": 1, "fraction": 0.653 }, ...],
+///   "bin_size": 10,
+///   "depths": [23, 31, ...]
+/// }
+/// ```
+///
+/// `start` and `end` are 0-based and half-open, `name` is the BED name if any, `breadth` holds
+/// one entry per [THRESHOLDS] and `bin_size`/`depths` are only present when the depth array
+/// was asked for, each depth merging `bin_size` positions.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageSummary {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub name: Option<String>,
+    pub mean: f64,
+    pub median: f64,
+    pub min: u32,
+    pub max: u32,
+    pub std_dev: f64,
+    pub breadth: Vec<Breadth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bin_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depths: Option<Vec<u64>>,
+}
+
+/// Fraction of positions covered at least `min_depth` deep
+#[derive(Debug, Clone, Serialize)]
+pub struct Breadth {
+    pub min_depth: u32,
+    pub fraction: f64,
+}
+
+impl CoverageSummary {
+    /// Summarize a region from the statistics of its depths
+    pub fn new(
+        chrom: String,
+        start: u32,
+        end: u32,
+        name: Option<String>,
+        stats: &DepthStats,
+    ) -> Self {
+        let breadth = THRESHOLDS
+            .iter()
+            .zip(stats.breadth)
+            .map(|(&min_depth, fraction)| Breadth {
+                min_depth,
+                fraction,
+            })
+            .collect();
+        Self {
+            chrom,
+            start,
+            end,
+            name,
+            mean: stats.mean,
+            median: stats.median,
+            min: stats.min,
+            max: stats.max,
+            std_dev: stats.std_dev,
+            breadth,
+            bin_size: None,
+            depths: None,
+        }
+    }
+
+    /// Attach the depth array, each value merging `bin_size` positions
+    pub fn with_depths(mut self, bin_size: u32, depths: Vec<u64>) -> Self {
+        self.bin_size = Some(bin_size);
+        self.depths = Some(depths);
+        self
+    }
+}
@@ -0,0 +1,200 @@
+use crate::track::Scale;
+use ratatui::style::Color;
+use std::fmt::Write;
+
+/// Width of one column of the view, in pixels
+const COLUMN_WIDTH: f64 = 4.0;
+/// Height of the bars of one input file
+const TRACK_HEIGHT: f64 = 160.0;
+/// Room left of the tracks for the depth labels
+const MARGIN_LEFT: f64 = 60.0;
+/// Room right of the tracks so the end label fits
+const MARGIN_RIGHT: f64 = 20.0;
+/// Room above the tracks for the title
+const MARGIN_TOP: f64 = 30.0;
+/// Room below the tracks for the coordinate labels
+const MARGIN_BOTTOM: f64 = 30.0;
+/// Room above each track for its name
+const TRACK_GAP: f64 = 20.0;
+
+/// One input file of the view, as drawn in the SVG
+pub struct SvgTrack<'a> {
+    pub name: &'a str,
+    /// one depth per column, the forward strand if `reverse` is set
+    pub forward: &'a [u64],
+    pub reverse: Option<&'a [u64]>,
+    /// depth drawn at full height
+    pub max: u64,
+    pub color: Color,
+    pub reverse_color: Color,
+}
+
+/// Render the tracks stacked over one coordinate axis from `start` to `end`
+pub fn render_svg(title: &str, start: u32, end: u32, tracks: &[SvgTrack], scale: Scale) -> String {
+    let columns = tracks.iter().map(|t| t.forward.len()).max().unwrap_or(0);
+    let plot_width = columns as f64 * COLUMN_WIDTH;
+    let width = MARGIN_LEFT + plot_width + MARGIN_RIGHT;
+    let height = MARGIN_TOP + tracks.len() as f64 * (TRACK_GAP + TRACK_HEIGHT) + MARGIN_BOTTOM;
+
+    let mut svg = String::new();
+    // writing to a String never fails
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="20" text-anchor="middle" font-size="14">{}</text>"#,
+        width / 2.0,
+        escape(title)
+    );
+
+    for (i, track) in tracks.iter().enumerate() {
+        let top = MARGIN_TOP + i as f64 * (TRACK_GAP + TRACK_HEIGHT) + TRACK_GAP;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}">{}</text>"#,
+            MARGIN_LEFT,
+            top - 5.0,
+            escape(track.name)
+        );
+        match track.reverse {
+            Some(reverse) => {
+                // forward grows up from the middle, reverse hangs down from it
+                let middle = top + TRACK_HEIGHT / 2.0;
+                let half = TRACK_HEIGHT / 2.0;
+                write_bars(
+                    &mut svg,
+                    track.forward,
+                    track.max,
+                    scale,
+                    middle,
+                    -half,
+                    track.color,
+                );
+                write_bars(
+                    &mut svg,
+                    reverse,
+                    track.max,
+                    scale,
+                    middle,
+                    half,
+                    track.reverse_color,
+                );
+                write_tick(&mut svg, top, &track.max.to_string());
+                write_tick(&mut svg, middle, "0");
+                write_tick(&mut svg, top + TRACK_HEIGHT, &track.max.to_string());
+            }
+            None => {
+                let bottom = top + TRACK_HEIGHT;
+                write_bars(
+                    &mut svg,
+                    track.forward,
+                    track.max,
+                    scale,
+                    bottom,
+                    -TRACK_HEIGHT,
+                    track.color,
+                );
+                write_tick(&mut svg, top, &track.max.to_string());
+                write_tick(&mut svg, bottom, "0");
+            }
+        }
+    }
+
+    // coordinate labels under the last track
+    let label_y = height - MARGIN_BOTTOM / 2.0;
+    let _ = writeln!(
+        svg,
+        r#"<text x="{MARGIN_LEFT}" y="{label_y}">{start}</text>"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{label_y}" text-anchor="middle">{}</text>"#,
+        MARGIN_LEFT + plot_width / 2.0,
+        // a flipped view has its end left of its start
+        start.min(end) + start.abs_diff(end) / 2
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{label_y}" text-anchor="end">{end}</text>"#,
+        MARGIN_LEFT + plot_width
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// one rect per non-zero column, growing from `base` by up to `full` pixels (negative is up)
+fn write_bars(
+    svg: &mut String,
+    data: &[u64],
+    max: u64,
+    scale: Scale,
+    base: f64,
+    full: f64,
+    color: Color,
+) {
+    let color = svg_color(color);
+    for (i, &value) in data.iter().enumerate() {
+        let length = scale.fraction(value, max) * full;
+        if length == 0.0 {
+            continue;
+        }
+        let y = if length < 0.0 { base + length } else { base };
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{y:.2}" width="{COLUMN_WIDTH}" height="{:.2}" fill="{color}"/>"#,
+            MARGIN_LEFT + i as f64 * COLUMN_WIDTH,
+            length.abs()
+        );
+    }
+}
+
+// a depth label left of the tracks
+fn write_tick(svg: &mut String, y: f64, label: &str) {
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+        MARGIN_LEFT - 5.0,
+        y + 4.0,
+        label
+    );
+}
+
+/// A terminal color as red, green and blue, from the usual xterm palette
+pub fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::Red => (0xcd, 0x00, 0x00),
+        Color::Green => (0x00, 0xcd, 0x00),
+        Color::Yellow => (0xcd, 0xcd, 0x00),
+        Color::Blue => (0x00, 0x00, 0xee),
+        Color::Magenta => (0xcd, 0x00, 0xcd),
+        Color::Cyan => (0x00, 0xcd, 0xcd),
+        Color::Gray => (0xe5, 0xe5, 0xe5),
+        Color::DarkGray => (0x7f, 0x7f, 0x7f),
+        Color::LightRed => (0xff, 0x00, 0x00),
+        Color::LightGreen => (0x00, 0xff, 0x00),
+        Color::LightYellow => (0xff, 0xff, 0x00),
+        Color::LightBlue => (0x5c, 0x5c, 0xff),
+        Color::LightMagenta => (0xff, 0x00, 0xff),
+        Color::LightCyan => (0x00, 0xff, 0xff),
+        Color::White => (0xff, 0xff, 0xff),
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => (0x7f, 0x7f, 0x7f),
+    }
+}
+
+// a terminal color as svg hex
+fn svg_color(color: Color) -> String {
+    let (r, g, b) = rgb(color);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// escape text for xml
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
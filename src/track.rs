@@ -0,0 +1,395 @@
+use ratatui::{
+    prelude::*,
+    symbols::{bar, Marker},
+    widgets::{
+        canvas::{self, Canvas, Points},
+        Widget,
+    },
+};
+
+/// Bar symbols from empty to full, in eighths of a cell
+const LEVELS: [&str; 9] = [
+    bar::NINE_LEVELS.empty,
+    bar::NINE_LEVELS.one_eighth,
+    bar::NINE_LEVELS.one_quarter,
+    bar::NINE_LEVELS.three_eighths,
+    bar::NINE_LEVELS.half,
+    bar::NINE_LEVELS.five_eighths,
+    bar::NINE_LEVELS.three_quarters,
+    bar::NINE_LEVELS.seven_eighths,
+    bar::NINE_LEVELS.full,
+];
+
+/// Which way the bars of a track grow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// bars stand on the bottom edge, like a sparkline
+    #[default]
+    Up,
+    /// bars hang from the top edge, mirroring a track drawn above
+    Down,
+}
+
+/// How depths map to bar heights
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// log2(depth + 1), so low coverage stays visible beside spikes
+    Log,
+}
+
+impl Scale {
+    /// Bar length in eighths of a cell, for a track `height` cells high reaching `max`
+    fn eighths(self, value: u64, max: u64, height: u64) -> u64 {
+        let value = value.min(max);
+        match self {
+            Scale::Linear => (value * height * 8).checked_div(max).unwrap_or(0),
+            Scale::Log => {
+                let full = (max as f64 + 1.0).log2();
+                if full == 0.0 {
+                    return 0;
+                }
+                ((value as f64 + 1.0).log2() / full * (height * 8) as f64) as u64
+            }
+        }
+    }
+
+    /// Bar length as a fraction of the full height, for drawing outside the terminal
+    pub fn fraction(self, value: u64, max: u64) -> f64 {
+        if max == 0 {
+            return 0.0;
+        }
+        let value = value.min(max);
+        match self {
+            Scale::Linear => value as f64 / max as f64,
+            Scale::Log => (value as f64 + 1.0).log2() / (max as f64 + 1.0).log2(),
+        }
+    }
+
+    /// Depth at which a bar reaches `num / den` of the track height
+    fn depth_at(self, num: u64, den: u64, max: u64) -> u64 {
+        match self {
+            Scale::Linear => max * num / den,
+            Scale::Log => ((max as f64 + 1.0).powf(num as f64 / den as f64) - 1.0).round() as u64,
+        }
+    }
+}
+
+/// Color ramp from low to high depth, as evenly spaced RGB stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gradient {
+    Viridis,
+    Magma,
+    Grayscale,
+}
+
+impl Gradient {
+    fn stops(self) -> &'static [(u8, u8, u8)] {
+        match self {
+            Gradient::Viridis => &[
+                (0x44, 0x01, 0x54),
+                (0x3b, 0x52, 0x8b),
+                (0x21, 0x91, 0x8c),
+                (0x5e, 0xc9, 0x62),
+                (0xfd, 0xe7, 0x25),
+            ],
+            Gradient::Magma => &[
+                (0x00, 0x00, 0x04),
+                (0x51, 0x12, 0x7c),
+                (0xb7, 0x37, 0x79),
+                (0xfc, 0x89, 0x61),
+                (0xfc, 0xfd, 0xbf),
+            ],
+            // starts above black so low columns stay visible on dark terminals
+            Gradient::Grayscale => &[(0x3a, 0x3a, 0x3a), (0xff, 0xff, 0xff)],
+        }
+    }
+
+    /// Color at `fraction` of the way from low to high, clamped to 0..=1
+    pub fn color(self, fraction: f64) -> Color {
+        let stops = self.stops();
+        let position = fraction.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let i = (position as usize).min(stops.len() - 2);
+        let t = position - i as f64;
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        let (from, to) = (stops[i], stops[i + 1]);
+        Color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+}
+
+/// A sparkline-like depth track with one bar per column.
+///
+/// Unlike ratatui's `Sparkline`, the bars can also hang downward.
+pub struct Track<'a> {
+    data: &'a [u64],
+    max: Option<u64>,
+    style: Style,
+    orientation: Orientation,
+    scale: Scale,
+    /// columns below this depth use their own style
+    low: Option<(u64, Style)>,
+    /// color each column by its height instead of the style color
+    gradient: Option<Gradient>,
+    /// part of each column drawn in its own style, from the base up to this value
+    shade: Option<(&'a [u64], Style)>,
+    /// columns drawn wholly in their own style, whatever their depth
+    masked: Option<(&'a [bool], Style)>,
+    /// draw a line through the columns in braille dots instead of bars
+    braille: bool,
+}
+
+impl<'a> Track<'a> {
+    pub fn new(data: &'a [u64]) -> Self {
+        Self {
+            data,
+            max: None,
+            style: Style::default(),
+            orientation: Orientation::default(),
+            scale: Scale::default(),
+            low: None,
+            gradient: None,
+            shade: None,
+            masked: None,
+            braille: false,
+        }
+    }
+
+    /// Value drawn as a full-height bar, defaults to the max of the data
+    pub fn max(mut self, max: u64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Color each column by its depth relative to the max
+    pub fn gradient(mut self, gradient: Option<Gradient>) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    /// Draw columns whose depth is below `threshold` with `style` instead
+    pub fn highlight_below(mut self, threshold: u64, style: Style) -> Self {
+        self.low = Some((threshold, style));
+        self
+    }
+
+    /// Draw the base of each column up to its value in `shade` with `style`, the cells at least
+    /// half under it
+    pub fn shade(mut self, shade: Option<&'a [u64]>, style: Style) -> Self {
+        self.shade = shade.map(|shade| (shade, style));
+        self
+    }
+
+    /// Draw the columns set in `masked` with `style`, before any other style
+    pub fn mask(mut self, masked: Option<&'a [bool]>, style: Style) -> Self {
+        self.masked = masked.map(|masked| (masked, style));
+        self
+    }
+
+    /// Draw the depths as a line of braille dots, two points across and four up each cell.
+    ///
+    /// Each segment takes the color of the column it starts from; the shading is not drawn.
+    pub fn braille(mut self, braille: bool) -> Self {
+        self.braille = braille;
+        self
+    }
+
+    /// Style of column `i` holding `value`, with the masked, low and gradient styles in that order
+    fn column_style(&self, i: usize, value: u64, max: u64) -> Style {
+        let masked_style = self
+            .masked
+            .and_then(|(masked, style)| masked.get(i).copied().unwrap_or(false).then_some(style));
+        match (masked_style, self.low, self.gradient) {
+            (Some(masked_style), ..) => masked_style,
+            (_, Some((threshold, low_style)), _) if value < threshold => low_style,
+            (_, _, Some(gradient)) => self
+                .style
+                .fg(gradient.color(self.scale.fraction(value, max))),
+            _ => self.style,
+        }
+    }
+
+    fn render_braille(&self, area: Rect, buf: &mut Buffer, max: u64) {
+        // each point sits at the middle of its column, at the fraction of the height its bar reaches
+        let points: Vec<(f64, f64, Color)> = self
+            .data
+            .iter()
+            .take(area.width as usize)
+            .enumerate()
+            .map(|(i, &value)| {
+                let y = self.scale.fraction(value, max);
+                let y = match self.orientation {
+                    Orientation::Up => y,
+                    Orientation::Down => -y,
+                };
+                let color = self.column_style(i, value, max).fg.unwrap_or(Color::Reset);
+                (i as f64 + 0.5, y, color)
+            })
+            .collect();
+        let y_bounds = match self.orientation {
+            Orientation::Up => [0.0, 1.0],
+            Orientation::Down => [-1.0, 0.0],
+        };
+        Canvas::default()
+            .marker(Marker::Braille)
+            .x_bounds([0.0, f64::from(area.width)])
+            .y_bounds(y_bounds)
+            .paint(|ctx| {
+                if let [(x, y, color)] = points[..] {
+                    ctx.draw(&Points {
+                        coords: &[(x, y)],
+                        color,
+                    });
+                }
+                for pair in points.windows(2) {
+                    let ((x1, y1, color), (x2, y2, _)) = (pair[0], pair[1]);
+                    ctx.draw(&canvas::Line::new(x1, y1, x2, y2, color));
+                }
+            })
+            .render(area, buf);
+    }
+}
+
+impl Widget for Track<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+
+        let max = self
+            .max
+            .unwrap_or_else(|| self.data.iter().copied().max().unwrap_or(1));
+        if self.braille {
+            self.render_braille(area, buf, max);
+            return;
+        }
+        let height = u64::from(area.height);
+        for (i, &value) in self.data.iter().take(area.width as usize).enumerate() {
+            let x = area.left() + i as u16;
+            let is_masked = self
+                .masked
+                .is_some_and(|(masked, _)| masked.get(i).copied().unwrap_or(false));
+            let style = self.column_style(i, value, max);
+            // bar length in eighths of a cell
+            let mut eighths = self.scale.eighths(value, max, height);
+            let shaded = match self.shade {
+                // masked and low columns stay wholly in their style
+                Some(_) if is_masked => None,
+                Some(_) if self.low.is_some_and(|(threshold, _)| value < threshold) => None,
+                Some((shade, shade_style)) => shade
+                    .get(i)
+                    .map(|&shade| (self.scale.eighths(shade, max, height), shade_style)),
+                None => None,
+            };
+            for j in 0..area.height {
+                let level = eighths.min(8) as usize;
+                eighths -= level as u64;
+                let style = match shaded {
+                    Some((shade, shade_style)) if u64::from(j) * 8 + 4 <= shade => shade_style,
+                    _ => style,
+                };
+                match self.orientation {
+                    Orientation::Up => {
+                        buf.get_mut(x, area.bottom() - 1 - j)
+                            .set_symbol(LEVELS[level])
+                            .set_style(style);
+                    }
+                    Orientation::Down => {
+                        // no top-anchored partial blocks, so draw the gap in reverse video
+                        buf.get_mut(x, area.top() + j)
+                            .set_symbol(LEVELS[8 - level])
+                            .set_style(style.add_modifier(Modifier::REVERSED));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width of the [YAxis] column, five digits and a tick
+pub const AXIS_WIDTH: u16 = 6;
+
+/// Depth tick labels to draw beside a [Track] of the same height, max and orientation.
+///
+/// A row is labelled with the true depth at which its bar cell is full, whatever the scale.
+pub struct YAxis {
+    max: u64,
+    style: Style,
+    orientation: Orientation,
+    scale: Scale,
+}
+
+impl YAxis {
+    pub fn new(max: u64) -> Self {
+        Self {
+            max,
+            style: Style::default(),
+            orientation: Orientation::default(),
+            scale: Scale::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl Widget for YAxis {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let height = u64::from(area.height);
+        let digits = area.width.saturating_sub(1) as usize;
+        // label about four rows, counting from the far end so the max is always shown
+        let step = (area.height / 4).max(1);
+        for j in 0..area.height {
+            let y = match self.orientation {
+                Orientation::Up => area.bottom() - 1 - j,
+                Orientation::Down => area.top() + j,
+            };
+            let text = if (area.height - 1 - j).is_multiple_of(step) {
+                let full_at = self.scale.depth_at(u64::from(j) + 1, height, self.max);
+                format!("{:>digits$}┤", short_depth(full_at))
+            } else {
+                format!("{:>digits$}│", "")
+            };
+            buf.set_stringn(area.x, y, text, area.width as usize, self.style);
+        }
+    }
+}
+
+/// Format a depth in at most five characters
+fn short_depth(depth: u64) -> String {
+    match depth {
+        0..=99_999 => depth.to_string(),
+        100_000..=99_999_999 => format!("{}k", depth / 1_000),
+        _ => format!("{}M", depth / 1_000_000),
+    }
+}
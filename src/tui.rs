@@ -1,54 +1,1070 @@
 use crate::cli;
-use anyhow::Result;
+use crate::svg::{render_svg, SvgTrack};
+use crate::track::{Gradient, Orientation, Scale, Track, YAxis, AXIS_WIDTH};
+use anyhow::{anyhow, Result};
 use ratatui::{
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+        cursor,
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers,
+            MouseButton, MouseEventKind,
+        },
         execute,
-        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+        terminal::{
+            self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+        },
     },
     prelude::*,
-    widgets::{Block, Paragraph, Sparkline},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Clear, Paragraph, Wrap},
 };
 use std::{
-    io,
-    time::{Duration, Instant},
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tcov::annotation::Feature;
+use tcov::cov::MAPQ_STEPS;
+use tcov::stats::{DepthStats, THRESHOLDS};
+use tcov::variant::{Variant, VariantKind};
+
+/// Minimum terminal width to draw the start and end labels
+const MIN_WIDTH: u16 = 18;
+
+/// Regions longer than this many screens of columns are binned before viewing
+const BIN_SCREENS: u32 = 100;
+
+/// Color of the GC track, kept out of [TRACK_COLORS]
+const GC_COLOR: Color = Color::LightYellow;
+
+/// Rows of the GC track under the coverage tracks
+const GC_HEIGHT: u16 = 4;
+
+/// Rows of the MAPQ track under each input file
+const MAPQ_HEIGHT: u16 = 3;
+
+/// Mapping quality drawn full height, unless a higher one is on screen
+const MAPQ_MAX: u64 = 60;
+
+/// Rows of the soft-clip track under each input file
+const SOFTCLIP_HEIGHT: u16 = 3;
+
+/// Color of the soft-clip tracks, the same for every input file
+const SOFTCLIP_COLOR: Color = Color::LightRed;
+
+/// Percentage of disagreeing reads from which a column gets a variant hint
+const HINT_MIN: u64 = 20;
+
+/// Percentage of disagreeing reads from which a variant hint is drawn as strong
+const HINT_STRONG: u64 = 50;
+
+/// Rows of the log2 ratio track, half above and half below zero
+const RATIO_HEIGHT: u16 = 4;
+
+/// log2 ratio drawn full height, beyond it bars are clipped
+const RATIO_MAX: f64 = 2.0;
+
+/// Color of the ratio bars above zero, more depth than the baseline
+const GAIN_COLOR: Color = Color::LightRed;
+
+/// Color of the ratio bars below zero, less depth than the baseline
+const LOSS_COLOR: Color = Color::LightBlue;
+
+/// Color of the part of a track made of reads passing the MAPQ of `--usable-mapq`
+const USABLE_COLOR: Color = Color::LightCyan;
+
+/// Percentage of N reference bases from which a data point is masked with `--mask-n`
+const N_MASK_MIN: u64 = 50;
+
+/// Color of the columns masked for an N reference
+const MASK_COLOR: Color = Color::DarkGray;
+
+/// Columns of each bar of the depth histogram, room for a five character label
+const HIST_BAR_WIDTH: u16 = 5;
+
+/// Marker of a variant under its column
+const VARIANT_MARKER: &str = "▲";
+
+/// Color of the feature bars of the annotation track
+const FEATURE_COLOR: Color = Color::Gray;
+
+/// Color of the window in the minimap, the rest of the region is dark gray
+const MINIMAP_COLOR: Color = Color::Cyan;
+
+/// Time the event loop waits for an event with `--redraw-on-event`, nothing is drawn meanwhile
+const IDLE_TICK: Duration = Duration::from_secs(5);
+
+/// Keys and what they do, listed in the help popup
+const KEYS: [(&str, &str); 32] = [
+    (
+        "◄ ► / h l",
+        "scroll the view, or move the cursor while it is shown",
+    ),
+    ("Shift ◄ ►", "scroll by a whole window"),
+    ("PgUp PgDn / b f", "scroll by a whole window"),
+    ("+ / =", "zoom in"),
+    ("-", "zoom out"),
+    ("0", "jump to the start of the region"),
+    ("$", "jump to the end of the region"),
+    ("g", "go to a position"),
+    ("c", "show or hide the cursor"),
+    ("s", "show or hide the statistics"),
+    ("v", "show or hide the variants"),
+    ("m", "show or hide the minimap"),
+    (
+        "d",
+        "switch between the tracks and the depth histogram of the region",
+    ),
+    ("L", "switch between linear and log scale"),
+    ("N", "switch between depth and percent of the window max"),
+    ("W / w", "widen or narrow the moving average of the depths"),
+    (
+        "U / u",
+        "raise or lower the MAPQ of the shaded usable depth",
+    ),
+    ("{ }", "lower or raise the depth spikes are clipped at"),
+    (
+        "D",
+        "switch the duplicate filter and count the depths again",
+    ),
+    (
+        "P",
+        "switch the proper pair filter and count the depths again",
+    ),
+    (
+        "M",
+        "switch the --min-mapq filter and count the depths again",
+    ),
+    (
+        "▲ ▼ / k j",
+        "raise or lower the depth below which columns are red",
+    ),
+    ("[ ]", "jump to the previous or next run below that depth"),
+    ("Tab / n", "next region"),
+    ("Shift Tab / p", "previous region"),
+    ("e", "export the view as svg"),
+    ("y", "copy the locus of the cursor or the window center"),
+    ("H", "show or hide the help line"),
+    ("?", "show this help"),
+    ("q", "quit"),
+    ("wheel", "scroll the view"),
+    ("drag", "pan the view"),
+];
+
+/// Colors of the tracks after the first, when several bam files are viewed
+const TRACK_COLORS: [cli::Color; 6] = [
+    cli::Color::blue,
+    cli::Color::green,
+    cli::Color::magenta,
+    cli::Color::yellow,
+    cli::Color::cyan,
+    cli::Color::lightblue,
+];
+
+/// Coverage depth of one input file over a region
+pub struct SampleCov {
+    /// file name shown beside its track
+    name: String,
+    data: Vec<u64>,
+    reverse: Option<Vec<u64>>,
+    stats: DepthStats,
+    /// ranges below `--min-cov`
+    low_runs: Vec<(u32, u32)>,
+    /// factor and unit of `--normalize`, if any
+    norm: Option<(f64, &'static str)>,
+    /// mean mapping quality of the reads over each data point
+    mapq: Option<Vec<u64>>,
+    /// soft-clipped read ends at each data point
+    softclips: Option<Vec<u64>>,
+    /// percentage of reads with an indel or an uncommon base at each data point
+    hints: Option<Vec<u64>>,
+    /// depth of the reads reaching each MAPQ of [MAPQ_STEPS] at each data point
+    usable: Option<Vec<Vec<u64>>>,
+    /// MAPQ-weighted depth of both strands at each data point, before rounding
+    weighted: Option<Vec<f32>>,
+    /// fraction of the region covered at least this deep, shown in the title
+    breadth: Option<(u32, f64)>,
+    /// reads counted over the region, shown with the statistics
+    reads: Option<u64>,
+}
+
+impl SampleCov {
+    pub fn new(name: String, data: Vec<u64>) -> Self {
+        Self {
+            name,
+            data,
+            reverse: None,
+            stats: DepthStats::default(),
+            low_runs: Vec::new(),
+            norm: None,
+            mapq: None,
+            softclips: None,
+            hints: None,
+            usable: None,
+            weighted: None,
+            breadth: None,
+            reads: None,
+        }
+    }
+
+    // read every per-point array right to left
+    fn flip(&mut self) {
+        self.data.reverse();
+        for data in [
+            &mut self.reverse,
+            &mut self.mapq,
+            &mut self.softclips,
+            &mut self.hints,
+        ] {
+            if let Some(data) = data.as_mut() {
+                data.reverse();
+            }
+        }
+        for depths in self.usable.iter_mut().flatten() {
+            depths.reverse();
+        }
+        if let Some(weighted) = self.weighted.as_mut() {
+            weighted.reverse();
+        }
+    }
+
+    /// Show the number of reads counted over the region with the statistics
+    pub fn with_reads(mut self, reads: u64) -> Self {
+        self.reads = Some(reads);
+        self
+    }
+
+    /// Show the fraction of the region covered at least `min_depth` deep in the title
+    pub fn with_breadth(mut self, min_depth: u32, fraction: f64) -> Self {
+        self.breadth = Some((min_depth, fraction));
+        self
+    }
+
+    /// Show the mean mapping quality of each position under the depth track
+    pub fn with_mapq(mut self, mapq: Vec<u64>) -> Self {
+        self.mapq = Some(mapq);
+        self
+    }
+
+    /// Show the soft-clipped read ends at each position under the depth track
+    pub fn with_softclips(mut self, softclips: Vec<u64>) -> Self {
+        self.softclips = Some(softclips);
+        self
+    }
+
+    /// Mark the positions where many reads disagree in a row under the depth track
+    pub fn with_hints(mut self, hints: Vec<u64>) -> Self {
+        self.hints = Some(hints);
+        self
+    }
+
+    /// Shade the depth of the reads reaching a MAPQ in the track, one array per [MAPQ_STEPS]
+    pub fn with_usable(mut self, usable: Vec<Vec<u64>>) -> Self {
+        self.usable = Some(usable);
+        self
+    }
+
+    /// Show the MAPQ-weighted depth of each position unrounded beside the drawn one
+    pub fn with_weighted(mut self, weighted: Vec<f32>) -> Self {
+        self.weighted = Some(weighted);
+        self
+    }
+
+    /// Show depths scaled by `factor` in `unit` beside the raw ones
+    pub fn with_normalization(mut self, factor: f64, unit: &'static str) -> Self {
+        self.norm = Some((factor, unit));
+        self
+    }
+
+    // scale from raw depth to the normalized unit, 1 without normalization
+    fn factor(&self) -> f64 {
+        self.norm.map_or(1.0, |(factor, _)| factor)
+    }
+
+    // a depth as text, with its normalized value if any
+    fn format_depth(&self, depth: u64) -> String {
+        match self.norm {
+            Some((factor, unit)) => format!("{} ({:.3} {})", depth, depth as f64 * factor, unit),
+            None => depth.to_string(),
+        }
+    }
+
+    /// Attach the depth statistics of the whole region
+    pub fn with_stats(mut self, stats: DepthStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Attach the ranges whose depth is below `--min-cov`
+    pub fn with_low_runs(mut self, low_runs: Vec<(u32, u32)>) -> Self {
+        self.low_runs = low_runs;
+        self
+    }
+
+    /// Split the coverage by strand, `data` then only holds the forward strand
+    pub fn with_reverse(mut self, reverse: Vec<u64>) -> Self {
+        self.reverse = Some(reverse);
+        self
+    }
+}
+
+/// Coverage depth of one region in every input file, ready to be viewed
+pub struct RegionCov {
+    chrom: String,
+    start: u32,
+    /// exclusive end, the last bin may hold fewer bases
+    end: u32,
+    legend: String,
+    /// number of bases behind each data point
+    bin_size: u32,
+    /// one per input file, all of the same length
+    samples: Vec<SampleCov>,
+    /// GC percentage of the reference at each data point
+    gc: Option<Vec<u64>>,
+    /// percentage of N reference bases at each data point, masked from about half
+    n_percent: Option<Vec<u64>>,
+    /// expected depth at each data point, in the unit of the drawn depths
+    baseline: Option<Vec<f64>>,
+    /// variants inside the region, marked under the tracks
+    variants: Option<Vec<Variant>>,
+    /// features overlapping the region, drawn as bars under the tracks
+    features: Option<Vec<Feature>>,
+}
+
+impl RegionCov {
+    pub fn new(
+        chrom: String,
+        start: u32,
+        end: u32,
+        legend: String,
+        samples: Vec<SampleCov>,
+    ) -> Self {
+        Self {
+            chrom,
+            start,
+            end,
+            legend,
+            bin_size: 1,
+            samples,
+            gc: None,
+            n_percent: None,
+            baseline: None,
+            variants: None,
+            features: None,
+        }
+    }
+
+    /// Expected depth of each position, drawn as the log2 ratio of each track to it.
+    ///
+    /// The baseline is compared with the normalized depths under `--normalize`.
+    pub fn with_baseline(mut self, baseline: Vec<f64>) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Features drawn as bars under the tracks
+    pub fn with_features(mut self, features: Vec<Feature>) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Variants marked under the tracks
+    pub fn with_variants(mut self, variants: Vec<Variant>) -> Self {
+        self.variants = Some(variants);
+        self
+    }
+
+    /// GC percentage drawn under the tracks, one value per data point once binned
+    pub fn with_gc(mut self, gc: Vec<u64>) -> Self {
+        self.gc = Some(gc);
+        self
+    }
+
+    /// Percentage of N reference bases, the mostly N data points are drawn muted; one value per
+    /// data point once binned
+    pub fn with_n_percent(mut self, n_percent: Vec<u64>) -> Self {
+        self.n_percent = Some(n_percent);
+        self
+    }
+
+    /// Number of bases behind each data point, the samples come binned as they were counted
+    pub fn with_bin_size(mut self, bin_size: u32) -> Self {
+        if bin_size > 1 {
+            // expected depths are averaged, like the depths they are compared with
+            self.baseline = self.baseline.take().map(|baseline| {
+                baseline
+                    .chunks(bin_size as usize)
+                    .map(|bin| bin.iter().sum::<f64>() / bin.len() as f64)
+                    .collect()
+            });
+            self.bin_size = bin_size;
+        }
+        self
+    }
+}
+
+/// Colors of `n` stacked tracks, the first file keeps the chosen color and the others take
+/// the next free ones
+pub fn track_colors(first: cli::Color, n: usize) -> Vec<Color> {
+    let others = TRACK_COLORS.into_iter().filter(|&c| c != first).cycle();
+    std::iter::once(first)
+        .chain(others)
+        .take(n)
+        .map(cli::Color::to_ratatui_color)
+        .collect()
+}
+
+/// Bin size that keeps a region of `len` bases within a few screens of columns,
+/// 1 if it already fits
+pub fn auto_bin_size(len: u32) -> u32 {
+    let width = terminal::size().map_or(80, |(width, _)| track_width(width));
+    let limit = BIN_SCREENS * u32::from(width.max(1));
+    if len > limit {
+        len.div_ceil(limit)
+    } else {
+        1
+    }
+}
+
+/// Read filters that can be switched in the view, the depth tracks are counted again on change
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LiveFilters {
+    /// duplicate reads are left out
+    pub dups: bool,
+    /// paired reads only count in a proper pair
+    pub proper_pairs: bool,
+    /// reads under `--min-mapq` are left out
+    pub mapq: bool,
+}
+
+/// Counts the depth tracks of every input file over `chrom`, `start` to exclusive `end` again
+/// with the given filters, binned by the given size, with low runs below the given depth
+pub type Reload<'a> =
+    &'a dyn Fn(&str, u32, u32, u32, Option<u32>, LiveFilters) -> Result<Vec<SampleCov>>;
+
+/// Display settings of the view, from the command line
+pub struct ViewOptions<'a> {
+    /// columns moved by one scroll step
+    pub step: u8,
+    pub color: cli::Color,
+    pub reverse_color: cli::Color,
+    /// color of the labels, axes and help text
+    pub label_color: cli::Color,
+    pub colormap: cli::Colormap,
+    /// bars or a braille line for the depth tracks
+    pub chart: cli::Chart,
+    /// start on the log2 scale
+    pub log: bool,
+    pub bin_stat: cli::BinStat,
+    /// data points averaged into each drawn one, 1 draws the raw depths
+    pub smooth: u32,
+    /// start with the statistics panel shown
+    pub stats: bool,
+    pub min_cov: Option<u32>,
+    /// draw the regions right to left, 5' to 3' on the minus strand
+    pub flip: bool,
+    /// depth at which bars are clipped, the stats keep the true depths
+    pub max_depth: Option<u32>,
+    /// bars of the depth histogram, as many as fit if not set
+    pub hist_bins: Option<u32>,
+    /// MAPQ of the depth shaded in the tracks, when it was counted
+    pub usable_mapq: Option<u8>,
+    /// milliseconds between redraws
+    pub tick_ms: u64,
+    /// draw only after an event, waking up every [IDLE_TICK] otherwise
+    pub redraw_on_event: bool,
+    /// capture the mouse, which disables text selection in the terminal
+    pub mouse: bool,
+    /// read filters the depths were counted with
+    pub filters: LiveFilters,
+    /// MAPQ reads need while the mapq filter is on
+    pub min_mapq: u8,
+    /// reads were weighted by their MAPQ
+    pub weighted: bool,
+    /// counts the depths of a region again when a filter is switched
+    pub reload: Reload<'a>,
+    /// file keeping the last viewed coordinate of each region across runs
+    pub session: Option<PathBuf>,
+    /// 1 to show positions 1-based like samtools, 0 to show them 0-based like BED
+    pub coord_base: u32,
+}
+
+/// All regions to view and the index of the one on screen
+struct App<'a> {
+    views: Vec<CovView>,
+    current: usize,
+    /// color of the coverage, or of the forward strand, for each input file
+    colors: Vec<Color>,
+    /// color of the reverse strand
+    reverse_color: Color,
+    /// color of the labels, axes and help text
+    label_color: Color,
+    /// colors by depth replacing the flat track colors, if any
+    gradient: Option<Gradient>,
+    /// draw the depth tracks as braille lines instead of bars
+    braille: bool,
+    /// how depths map to bar heights
+    scale: Scale,
+    /// how depths are merged into one column when zoomed out
+    bin_stat: cli::BinStat,
+    /// width of the moving average drawn instead of the depths, odd, 1 for none
+    smooth: u32,
+    /// whether the statistics panel is shown
+    show_stats: bool,
+    /// whether variant markers are drawn
+    show_variants: bool,
+    /// whether the minimap of the whole region is drawn
+    show_minimap: bool,
+    /// whether the depth histogram of the region is drawn instead of the tracks
+    show_hist: bool,
+    /// bars of the depth histogram, as many as fit if not set
+    hist_bins: Option<u32>,
+    /// whether the key help is shown in the bottom row
+    show_help: bool,
+    /// whether the popup listing every key is open
+    show_keys: bool,
+    /// depth drawn full height at most, deeper columns are clipped
+    max_depth: Option<u32>,
+    /// index in [MAPQ_STEPS] of the MAPQ the shaded depth reaches, if it was counted
+    usable_step: Option<usize>,
+    /// read filters chosen with D, P and M, each region is counted again with them when shown
+    filters: LiveFilters,
+    /// MAPQ reads need while the mapq filter is on
+    min_mapq: u8,
+    /// reads were weighted by their MAPQ, noted in the legend
+    weighted: bool,
+    reload: Reload<'a>,
+    /// position typed so far in the go-to prompt, if it is open
+    prompt: Option<String>,
+    /// error shown in the help line until the next key press
+    message: Option<String>,
+    /// column of the pointer while the tracks are dragged with the mouse
+    drag_column: Option<u16>,
+    /// files exported so far, reported on exit
+    saved: Vec<PathBuf>,
+    /// loci copied so far, reported on exit for terminals that ignore the clipboard request
+    copied: Vec<String>,
+    /// added to the 0-based positions shown and taken from the typed ones, from --coord-base
+    coord_base: u32,
+}
+
+impl<'a> App<'a> {
+    fn new(covs: Vec<RegionCov>, init_width: u16, options: ViewOptions<'a>) -> Self {
+        let views: Vec<CovView> = covs
+            .into_iter()
+            .map(|cov| {
+                CovView::new(
+                    cov,
+                    init_width,
+                    options.min_cov,
+                    options.flip,
+                    options.filters,
+                )
+            })
+            .collect();
+        let n_samples = views.first().map_or(1, |view| view.samples.len());
+        // mapq tracks take the colors after those of the depth tracks
+        let has_mapq = views
+            .iter()
+            .any(|view| view.samples.iter().any(|s| s.mapq.is_some()));
+        let n_tracks = if has_mapq { 2 * n_samples } else { n_samples };
+        let colors = track_colors(options.color, n_tracks);
+        Self {
+            views,
+            current: 0,
+            colors,
+            reverse_color: options.reverse_color.to_ratatui_color(),
+            label_color: options.label_color.to_ratatui_color(),
+            gradient: match options.colormap {
+                cli::Colormap::none => None,
+                cli::Colormap::viridis => Some(Gradient::Viridis),
+                cli::Colormap::magma => Some(Gradient::Magma),
+                cli::Colormap::grayscale => Some(Gradient::Grayscale),
+            },
+            braille: matches!(options.chart, cli::Chart::braille),
+            scale: if options.log {
+                Scale::Log
+            } else {
+                Scale::Linear
+            },
+            bin_stat: options.bin_stat,
+            // an even width has no center, so it takes one more point
+            smooth: options.smooth | 1,
+            show_stats: options.stats,
+            show_variants: true,
+            show_minimap: true,
+            show_hist: false,
+            hist_bins: options.hist_bins,
+            show_help: true,
+            show_keys: false,
+            max_depth: options.max_depth,
+            // --usable-mapq was checked to be one of the steps
+            usable_step: options
+                .usable_mapq
+                .and_then(|mapq| MAPQ_STEPS.iter().position(|&step| step == mapq)),
+            filters: options.filters,
+            min_mapq: options.min_mapq,
+            weighted: options.weighted,
+            reload: options.reload,
+            prompt: None,
+            message: None,
+            drag_column: None,
+            saved: Vec::new(),
+            copied: Vec::new(),
+            coord_base: options.coord_base,
+        }
+    }
+
+    fn view(&self) -> &CovView {
+        &self.views[self.current]
+    }
+
+    fn view_mut(&mut self) -> &mut CovView {
+        &mut self.views[self.current]
+    }
+
+    // switch to the next region, wrapping around
+    fn next_region(&mut self) {
+        self.current = (self.current + 1) % self.views.len();
+    }
+
+    // switch to the previous region, wrapping around
+    fn prev_region(&mut self) {
+        self.current = (self.current + self.views.len() - 1) % self.views.len();
+    }
+
+    // switch between linear and log2 bar heights
+    fn toggle_log(&mut self) {
+        self.scale = match self.scale {
+            Scale::Linear => Scale::Log,
+            Scale::Log => Scale::Linear,
+        };
+    }
+
+    // the depth drawn full height in each file, clipped to --max-depth, or each file's own
+    // window max when drawn as a percentage of it
+    fn drawn_maxes(&self, windows: &[(Vec<u64>, Option<Vec<u64>>)]) -> Vec<u64> {
+        if self.view().display == DepthDisplay::WindowPercent {
+            return windows
+                .iter()
+                .map(|(forward, reverse)| {
+                    let max = forward.iter().chain(reverse.iter().flatten()).max();
+                    max.copied().unwrap_or(0).max(1)
+                })
+                .collect();
+        }
+        let maxes = self.view().shared_maxes(windows);
+        match self.max_depth {
+            Some(cap) => maxes.into_iter().map(|max| max.min(cap.into())).collect(),
+            None => maxes,
+        }
+    }
+
+    // clip more of the spikes, halving the depth drawn full height
+    fn lower_max_depth(&mut self) {
+        let cap = self.max_depth.map_or_else(|| self.window_max(), u64::from);
+        self.max_depth = Some((cap / 2).max(1) as u32);
+    }
+
+    // clip less, doubling the depth drawn full height until nothing on screen is clipped
+    fn raise_max_depth(&mut self) {
+        let Some(cap) = self.max_depth else {
+            return;
+        };
+        let cap = cap.saturating_mul(2);
+        self.max_depth = (u64::from(cap) < self.window_max()).then_some(cap);
+    }
+
+    // shade the depth of the reads of the next or previous MAPQ step
+    fn step_usable_mapq(&mut self, up: bool) {
+        if let Some(step) = self.usable_step.as_mut() {
+            *step = if up {
+                (*step + 1).min(MAPQ_STEPS.len() - 1)
+            } else {
+                step.saturating_sub(1)
+            };
+        }
+    }
+
+    // switch the mapq filter, there is none to switch without --min-mapq
+    fn toggle_mapq_filter(&mut self) {
+        if self.min_mapq == 0 {
+            self.message = Some("no --min-mapq to switch".to_string());
+        } else {
+            self.filters.mapq = !self.filters.mapq;
+        }
+    }
+
+    // count the depths of the region on screen again when they were counted with other filters
+    // than the chosen ones; the other regions follow once shown
+    fn sync_filters(&mut self) {
+        let filters = self.filters;
+        let view = self.view();
+        let shown = view.filters;
+        if shown == filters {
+            return;
+        }
+        let counted = (self.reload)(
+            &view.chrom,
+            view.start,
+            view.end,
+            view.bin_size,
+            view.min_cov,
+            filters,
+        );
+        match counted {
+            Ok(samples) => self.view_mut().set_depths(samples, filters),
+            Err(err) => {
+                // back to the filters on screen, so the next key doesn't fail again
+                self.filters = shown;
+                self.message = Some(format!("reload failed: {}", err));
+            }
+        }
+    }
+
+    // the usable depth of a file in the window, smoothed like its track, if it was counted
+    fn usable_window(&self, sample: &SampleCov) -> Option<Vec<u64>> {
+        let step = self.usable_step?;
+        let usable = sample.usable.as_ref()?;
+        Some(
+            self.view()
+                .smoothed_window(&usable[step], self.bin_stat, self.smooth),
+        )
+    }
+
+    // the highest unclipped depth drawn full height on screen
+    fn window_max(&self) -> u64 {
+        let view = self.view();
+        let windows = view.sample_windows(self.bin_stat, self.smooth);
+        view.shared_maxes(&windows).into_iter().max().unwrap_or(0)
+    }
+
+    // copy `chrom:pos` of the cursor, or else the window center, to the clipboard
+    fn copy_locus(&mut self) -> Result<()> {
+        let view = self.view();
+        let index = view
+            .cursor_pos
+            .unwrap_or((view.view_start + view.view_end) / 2);
+        let locus = format!("{}:{}", view.chrom, view.coord(index) + self.coord_base);
+        // an OSC 52 request to the terminal, which also reaches the local clipboard over ssh
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", base64(locus.as_bytes()))?;
+        stdout.flush()?;
+        self.message = Some(format!("copied {}", locus));
+        self.copied.push(locus);
+        Ok(())
+    }
+
+    // write the window on screen to an svg named after the region and the time
+    fn export_svg(&mut self) -> Result<()> {
+        let view = self.view();
+        let windows = view.sample_windows(self.bin_stat, self.smooth);
+        let maxes = self.drawn_maxes(&windows);
+        let tracks: Vec<SvgTrack> = windows
+            .iter()
+            .zip(&view.samples)
+            .zip(&maxes)
+            .enumerate()
+            .map(|(i, (((forward, reverse), sample), &max))| SvgTrack {
+                name: &sample.name,
+                forward,
+                reverse: reverse.as_deref(),
+                max,
+                color: self.colors[i % self.colors.len()],
+                reverse_color: self.reverse_color,
+            })
+            .collect();
+        let (start, end) = view.window_labels(self.coord_base);
+        let title = format!("{} ({}:{}-{})", view.legend, view.chrom, start, end);
+        let svg = render_svg(&title, start, end, &tracks, self.scale);
+
+        // keep the file name safe whatever the contig is called
+        let chrom: String = view
+            .chrom
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = PathBuf::from(format!("{}_{}-{}_{}.svg", chrom, start, end, timestamp));
+        fs::write(&path, svg)?;
+        self.message = Some(format!("saved {}", path.display()));
+        self.saved.push(path);
+        Ok(())
+    }
+
+    // jump to the next or previous run below --min-cov
+    fn jump_gap(&mut self, forward: bool, curr_view_size: u16) {
+        let Some(min_cov) = self.view().min_cov else {
+            self.message = Some("set --min-cov or press ▲ to jump between gaps".to_string());
+            return;
+        };
+        if !self.view_mut().jump_gap(forward, curr_view_size) {
+            let direction = if forward { "after" } else { "before" };
+            self.message = Some(format!("no gap below {}x {} here", min_cov, direction));
+        }
+    }
+
+    // jump to the position typed in the prompt
+    fn submit_prompt(&mut self, curr_view_size: u16) {
+        let Some(input) = self.prompt.take() else {
+            return;
+        };
+        match input.replace(',', "").parse::<u32>() {
+            Ok(pos) => {
+                let pos = pos.saturating_sub(self.coord_base);
+                if !self.view_mut().goto(pos, curr_view_size) {
+                    self.message = Some(format!("{} is outside the region, clamped", input));
+                }
+            }
+            Err(_) => self.message = Some(format!("invalid position: {}", input)),
+        }
+    }
+}
+
+/// What the height of the bars shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DepthDisplay {
+    /// the depth, on a scale shared by all files
+    Depth,
+    /// the percentage of the highest depth on screen in each file, to compare shapes
+    WindowPercent,
+}
 
 /// A struct to hold the data and view of the coverage data
 struct CovView {
-    data: Vec<u64>,
+    chrom: String,
     legend: String,
+    /// one per input file, scrolled and zoomed together
+    samples: Vec<SampleCov>,
+    /// GC percentage of the reference at each data point
+    gc: Option<Vec<u64>>,
+    /// percentage of N reference bases at each data point
+    n_percent: Option<Vec<u64>>,
+    /// expected depth at each data point, the tracks are compared with
+    baseline: Option<Vec<f64>>,
+    /// variants inside the region, marked under the tracks
+    variants: Option<Vec<Variant>>,
+    /// features overlapping the region, drawn as bars under the tracks
+    features: Option<Vec<Feature>>,
     view_start: u32,
     view_end: u32,
-    label_start: u32,
+    /// coordinate of the first data point
+    start: u32,
+    /// exclusive end of the region
+    end: u32,
+    /// number of bases behind each data point
+    bin_size: u32,
+    /// number of data points drawn in one column
+    zoom: u32,
+    /// data index under the cursor, if the cursor is shown
+    cursor_pos: Option<u32>,
+    /// track column under the mouse pointer, if it rests right of the y-axis
+    hover_column: Option<u16>,
+    /// depth below which columns are drawn in red, from --min-cov and moved with ▲ ▼
+    min_cov: Option<u32>,
+    /// whether the data runs right to left, from the end of the region to its start
+    flipped: bool,
+    /// data index ranges below `min_cov` in any input file, sorted and merged
+    gaps: Vec<(u32, u32)>,
+    /// how the bars are scaled, switched with N, the data is left as is
+    display: DepthDisplay,
+    /// read filters the depths were counted with
+    filters: LiveFilters,
 }
 
 impl CovView {
     // create new instance
-    fn new(data: Vec<u64>, legend: String, init_width: u16, label_start: u32) -> Self {
-        let view_end = if data.len() > init_width.into() {
+    fn new(
+        mut cov: RegionCov,
+        init_width: u16,
+        min_cov: Option<u32>,
+        flipped: bool,
+        filters: LiveFilters,
+    ) -> Self {
+        // every per-point array is read right to left, coordinates are mapped back on use
+        if flipped {
+            for sample in cov.samples.iter_mut() {
+                sample.flip();
+            }
+            if let Some(gc) = cov.gc.as_mut() {
+                gc.reverse();
+            }
+            if let Some(n_percent) = cov.n_percent.as_mut() {
+                n_percent.reverse();
+            }
+            if let Some(baseline) = cov.baseline.as_mut() {
+                baseline.reverse();
+            }
+        }
+        let data_len = cov.samples.first().map_or(0, |s| s.data.len());
+        let view_end = if data_len > init_width.into() {
             init_width as u32
         } else {
-            data.len() as u32
+            data_len as u32
         };
-        Self {
-            data,
-            legend,
+        let mut view = Self {
+            chrom: cov.chrom,
+            legend: cov.legend,
+            samples: cov.samples,
+            gc: cov.gc,
+            n_percent: cov.n_percent,
+            baseline: cov.baseline,
+            variants: cov.variants,
+            features: cov.features,
             view_start: 0,
             view_end,
-            label_start,
+            start: cov.start,
+            end: cov.end,
+            bin_size: cov.bin_size,
+            zoom: 1,
+            cursor_pos: None,
+            hover_column: None,
+            min_cov,
+            flipped,
+            gaps: Vec::new(),
+            display: DepthDisplay::Depth,
+            filters,
+        };
+        view.update_gaps();
+        view
+    }
+
+    // replace each file with its depths and tracks counted again with `filters`, binned like
+    // the others and flipped if they are
+    fn set_depths(&mut self, samples: Vec<SampleCov>, filters: LiveFilters) {
+        for (sample, mut counted) in self.samples.iter_mut().zip(samples) {
+            if self.flipped {
+                counted.flip();
+            }
+            *sample = counted;
+        }
+        self.filters = filters;
+        self.update_gaps();
+    }
+
+    // the low runs of every file, as data indexes, merged where they overlap
+    fn update_gaps(&mut self) {
+        let mut runs: Vec<(u32, u32)> = self
+            .samples
+            .iter()
+            .flat_map(|sample| &sample.low_runs)
+            .map(|&(start, end)| {
+                let first = self.data_index((start - self.start) / self.bin_size);
+                let last = self.data_index((end - 1 - self.start) / self.bin_size);
+                (first.min(last), first.max(last) + 1)
+            })
+            .collect();
+        runs.sort_unstable();
+        self.gaps.clear();
+        for (start, end) in runs {
+            match self.gaps.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => self.gaps.push((start, end)),
+            }
         }
     }
 
-    // update `view_start`  `view_end` `label_start`
+    // switch the bars between the depth and the percentage of the window max
+    fn toggle_display(&mut self) {
+        self.display = match self.display {
+            DepthDisplay::Depth => DepthDisplay::WindowPercent,
+            DepthDisplay::WindowPercent => DepthDisplay::Depth,
+        };
+    }
+
+    // move the depth below which columns are red by about a tenth, starting at 1x;
+    // the low runs are found again in the data points, so a whole bin is low or not
+    fn step_min_cov(&mut self, up: bool) {
+        let min_cov = match self.min_cov {
+            Some(min_cov) if up => min_cov + (min_cov / 10).max(1),
+            Some(min_cov) => min_cov.saturating_sub((min_cov / 10).max(1)),
+            None if up => 1,
+            None => return,
+        };
+        self.min_cov = Some(min_cov);
+        let low_runs: Vec<Vec<(u32, u32)>> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let mut runs: Vec<(u32, u32)> = Vec::new();
+                // runs are in coordinate order, whichever way the data runs
+                for i in 0..self.data_len() {
+                    let index = self.data_index(i) as usize;
+                    let depth =
+                        sample.data[index] + sample.reverse.as_ref().map_or(0, |r| r[index]);
+                    if depth >= u64::from(min_cov) {
+                        continue;
+                    }
+                    let start = self.start + i * self.bin_size;
+                    let end = (start + self.bin_size).min(self.end);
+                    match runs.last_mut() {
+                        Some((_, last)) if *last == start => *last = end,
+                        _ => runs.push((start, end)),
+                    }
+                }
+                runs
+            })
+            .collect();
+        for (sample, runs) in self.samples.iter_mut().zip(low_runs) {
+            sample.low_runs = runs;
+        }
+        self.update_gaps();
+    }
+
+    // move the window to start at the next gap after the cursor or the window start,
+    // or the previous one before it; return false if there is none
+    fn jump_gap(&mut self, forward: bool, curr_view_size: u16) -> bool {
+        let here = self.cursor_pos.unwrap_or(self.view_start);
+        let gap = if forward {
+            self.gaps.iter().find(|(start, _)| *start > here)
+        } else {
+            self.gaps.iter().rev().find(|(start, _)| *start < here)
+        };
+        let Some(&(start, _)) = gap else {
+            return false;
+        };
+        self.start_at(start, curr_view_size);
+        if self.cursor_pos.is_some() {
+            self.cursor_pos = Some(start);
+        }
+        true
+    }
+
+    // move the window so it starts at the data index, as far as the region allows
+    fn start_at(&mut self, index: u32, curr_view_size: u16) {
+        let data_len = self.data_len();
+        let span = u32::min(curr_view_size as u32 * self.zoom, data_len);
+        self.view_start = u32::min(index, data_len - span);
+        self.view_end = self.view_start + span;
+    }
+
+    // fit the window to a new terminal width, keeping its start unless it would run past
+    // the end, and keep the cursor inside it
+    fn resize(&mut self, curr_view_size: u16) {
+        self.start_at(self.view_start, curr_view_size);
+        if let Some(cursor) = self.cursor_pos {
+            if self.view_end > self.view_start {
+                self.cursor_pos = Some(cursor.clamp(self.view_start, self.view_end - 1));
+            }
+        }
+    }
+
+    // the region as written in the session file
+    fn session_key(&self) -> String {
+        format!("{}:{}-{}", self.chrom, self.start, self.end)
+    }
+
+    // update `view_start`  `view_end`
     fn move_view(&mut self, direction: i32, curr_view_size: u16) {
-        // diff between label_start and view_start
-        let label_view_diff = self.label_start as i32 - self.view_start as i32;
-        let curr_view_size = curr_view_size as u32;
-        let data_len = self.data.len() as u32;
+        // bases in the window and in a step at the current zoom
+        let curr_view_size = curr_view_size as u32 * self.zoom;
+        let data_len = self.data_len();
 
         // get step size == abs(direction)
-        let step_size = direction.unsigned_abs();
+        let step_size = direction.unsigned_abs() * self.zoom;
 
         if direction < 0 && self.view_start > 0 {
             // sub
@@ -58,95 +1074,580 @@ impl CovView {
         } else if direction > 0 && self.view_end < data_len {
             if self.view_end + step_size > data_len {
                 // check boundary
-                // compute the sub
-                let sub = data_len - self.view_end;
-                // touch the bottom
+                // touch the bottom, keeping the window size
                 self.view_end = data_len;
-                // update view_start
-                self.view_start = self.view_start.saturating_sub(sub);
+                self.view_start = data_len.saturating_sub(curr_view_size);
             } else {
                 self.view_end += step_size;
-                self.view_start = self.view_end - curr_view_size;
+                self.view_start = self.view_end.saturating_sub(curr_view_size);
             }
         }
-        // update label_start
-        self.label_start = self.view_start + label_view_diff as u32;
+    }
+
+    // number of data points, the same in every input file
+    fn data_len(&self) -> u32 {
+        self.samples.first().map_or(0, |s| s.data.len() as u32)
+    }
+
+    // data index of the `i`th data point from the region start, and back
+    fn data_index(&self, i: u32) -> u32 {
+        if self.flipped {
+            self.data_len() - 1 - i
+        } else {
+            i
+        }
+    }
+
+    // coordinate of the first base behind a data index
+    fn coord(&self, index: u32) -> u32 {
+        self.start + self.data_index(index) * self.bin_size
+    }
+
+    // coordinate at the left edge of a data index, `data_len` for the right edge of the last;
+    // a flipped view counts down from the region end
+    fn edge(&self, index: u32) -> u32 {
+        let points = if self.flipped {
+            self.data_len() - index
+        } else {
+            index
+        };
+        (self.start + points * self.bin_size).min(self.end)
+    }
+
+    // data index showing coordinate `pos`, if it is in the region
+    fn index_of(&self, pos: u32) -> Option<u32> {
+        let i = pos.checked_sub(self.start)? / self.bin_size;
+        (i < self.data_len()).then(|| self.data_index(i))
+    }
+
+    // data index under the mouse pointer, if it rests on a drawn column
+    fn hover_pos(&self) -> Option<u32> {
+        let index = self.view_start + u32::from(self.hover_column?) * self.zoom;
+        (index < self.view_end).then_some(index)
+    }
+
+    // show or hide the cursor, it appears in the middle of the window
+    fn toggle_cursor(&mut self) {
+        self.cursor_pos = match self.cursor_pos {
+            Some(_) => None,
+            None if self.view_end > self.view_start => Some((self.view_start + self.view_end) / 2),
+            None => None,
+        };
+    }
+
+    // move the cursor by columns, scrolling the view only when it leaves the window
+    fn move_cursor(&mut self, columns: i32, curr_view_size: u16) {
+        let Some(cursor) = self.cursor_pos else {
+            return;
+        };
+        let last = self.data_len().saturating_sub(1);
+        let step = columns.unsigned_abs() * self.zoom;
+        let cursor = if columns < 0 {
+            cursor.saturating_sub(step)
+        } else {
+            u32::min(cursor + step, last)
+        };
+        if cursor < self.view_start {
+            self.move_view(
+                -((self.view_start - cursor).div_ceil(self.zoom) as i32),
+                curr_view_size,
+            );
+        } else if cursor >= self.view_end {
+            self.move_view(
+                (cursor + 1 - self.view_end).div_ceil(self.zoom) as i32,
+                curr_view_size,
+            );
+        }
+        self.cursor_pos = Some(cursor);
+    }
+
+    // show more detail, halving the bases per column
+    fn zoom_in(&mut self, curr_view_size: u16) {
+        if self.zoom > 1 {
+            self.set_zoom(self.zoom / 2, curr_view_size);
+        }
+    }
+
+    // show more bases, doubling the bases per column until the whole region fits
+    fn zoom_out(&mut self, curr_view_size: u16) {
+        if (curr_view_size as u32 * self.zoom) < self.data_len() {
+            self.set_zoom(self.zoom * 2, curr_view_size);
+        }
+    }
+
+    // resize the window around its center for the new zoom
+    fn set_zoom(&mut self, zoom: u32, curr_view_size: u16) {
+        let center = (self.view_start + self.view_end) / 2;
+        self.zoom = zoom;
+        self.center_on(center, curr_view_size);
+    }
+
+    // snap the window to the start of the region
+    fn jump_start(&mut self, curr_view_size: u16) {
+        self.center_on(0, curr_view_size);
+    }
+
+    // snap the window to the end of the region
+    fn jump_end(&mut self, curr_view_size: u16) {
+        self.center_on(self.data_len(), curr_view_size);
+    }
+
+    // center the window on an absolute coordinate, clamped to the region;
+    // return false if it had to be clamped
+    fn goto(&mut self, pos: u32, curr_view_size: u16) -> bool {
+        let last =
+            self.start + self.data_len().saturating_sub(1) * self.bin_size + self.bin_size - 1;
+        let clamped = pos.clamp(self.start, last);
+        self.center_on(
+            self.data_index((clamped - self.start) / self.bin_size),
+            curr_view_size,
+        );
+        clamped == pos
+    }
+
+    // the window edges as labeled left to right, the first and last base of the window when
+    // counted from 1, the half-open edges when counted from 0
+    fn window_labels(&self, coord_base: u32) -> (u32, u32) {
+        let (left, right) = (self.edge(self.view_start), self.edge(self.view_end));
+        if left <= right {
+            (left + coord_base, right)
+        } else {
+            (left, right + coord_base)
+        }
+    }
+
+    // move the window so the data index is centered, as far as the region allows
+    fn center_on(&mut self, index: u32, curr_view_size: u16) {
+        let data_len = self.data_len();
+        let span = u32::min(curr_view_size as u32 * self.zoom, data_len);
+        self.view_start = u32::min(index.saturating_sub(span / 2), data_len - span);
+        self.view_end = self.view_start + span;
+    }
+
+    // column of the window showing coordinate `pos`, if it is on screen
+    fn column_of(&self, pos: u32) -> Option<u16> {
+        let index = self.index_of(pos)?;
+        if !(self.view_start..self.view_end).contains(&index) {
+            return None;
+        }
+        Some(((index - self.view_start) / self.zoom) as u16)
+    }
+
+    // first and last columns of the window showing coordinates `start` to exclusive `end`,
+    // if any of them is on screen
+    fn column_span(&self, start: u32, end: u32) -> Option<(u16, u16)> {
+        let first = start.saturating_sub(self.start) / self.bin_size;
+        let last = end.saturating_sub(1).checked_sub(self.start)? / self.bin_size;
+        let last = last.min(self.data_len().checked_sub(1)?);
+        if first > last {
+            return None;
+        }
+        let (first, last) = if self.flipped {
+            (self.data_index(last), self.data_index(first))
+        } else {
+            (first, last)
+        };
+        if last < self.view_start || first >= self.view_end {
+            return None;
+        }
+        let first = first.max(self.view_start);
+        let last = last.min(self.view_end - 1);
+        Some((
+            ((first - self.view_start) / self.zoom) as u16,
+            ((last - self.view_start) / self.zoom) as u16,
+        ))
+    }
+
+    // the depths in the window, one value per column
+    fn window(&self, data: &[u64], stat: cli::BinStat) -> Vec<u64> {
+        bin_depths(
+            &data[self.view_start as usize..self.view_end as usize],
+            self.zoom as usize,
+            stat,
+        )
+    }
+
+    // log2 of the depth of a file over the expected depth, both strands together, from data
+    // index `first` to exclusive `last`; none where nothing is expected
+    fn log2_ratio(
+        &self,
+        sample: &SampleCov,
+        baseline: &[f64],
+        first: u32,
+        last: u32,
+    ) -> Option<f64> {
+        let range = first as usize..last as usize;
+        let expected: f64 = baseline[range.clone()].iter().sum();
+        if expected <= 0.0 {
+            return None;
+        }
+        let forward: u64 = sample.data[range.clone()].iter().sum();
+        let reverse: u64 = sample
+            .reverse
+            .as_ref()
+            .map_or(0, |reverse| reverse[range].iter().sum());
+        let observed = (forward + reverse) as f64 * sample.factor();
+        // no depth at all is clipped like any deep loss
+        Some((observed / expected).log2().clamp(-RATIO_MAX, RATIO_MAX))
+    }
+
+    // the log2 ratios of a file in the window, one per column
+    fn ratio_window(&self, sample: &SampleCov, baseline: &[f64]) -> Vec<Option<f64>> {
+        (self.view_start..self.view_end)
+            .step_by(self.zoom as usize)
+            .map(|first| {
+                let last = (first + self.zoom).min(self.view_end);
+                self.log2_ratio(sample, baseline, first, last)
+            })
+            .collect()
+    }
+
+    // the window of every input file, forward or unstranded first, averaged over `smooth`
+    // data points around each one before they are merged into columns
+    fn sample_windows(&self, stat: cli::BinStat, smooth: u32) -> Vec<(Vec<u64>, Option<Vec<u64>>)> {
+        self.samples
+            .iter()
+            .map(|sample| {
+                let forward = self.smoothed_window(&sample.data, stat, smooth);
+                let reverse = sample
+                    .reverse
+                    .as_ref()
+                    .map(|reverse| self.smoothed_window(reverse, stat, smooth));
+                (forward, reverse)
+            })
+            .collect()
+    }
+
+    // the window of centered moving averages, reaching past the window edges where the
+    // region goes on so scrolling doesn't change the values on screen
+    fn smoothed_window(&self, data: &[u64], stat: cli::BinStat, smooth: u32) -> Vec<u64> {
+        if smooth <= 1 {
+            return self.window(data, stat);
+        }
+        let half = (smooth / 2) as usize;
+        let (start, end) = (self.view_start as usize, self.view_end as usize);
+        let from = start.saturating_sub(half);
+        let to = (end + half).min(data.len());
+        // sums[i] is the sum of the data from `from` to `from + i`, exclusive
+        let mut sums = Vec::with_capacity(to - from + 1);
+        sums.push(0);
+        for &depth in &data[from..to] {
+            sums.push(sums[sums.len() - 1] + depth);
+        }
+        let averages: Vec<u64> = (start..end)
+            .map(|i| {
+                let lo = i.saturating_sub(half).max(from) - from;
+                let hi = (i + half + 1).min(to) - from;
+                (sums[hi] - sums[lo]) / (hi - lo) as u64
+            })
+            .collect();
+        bin_depths(&averages, self.zoom as usize, stat)
+    }
+
+    // the raw depth drawn full height in each file, so that all files share one scale
+    fn shared_maxes(&self, windows: &[(Vec<u64>, Option<Vec<u64>>)]) -> Vec<u64> {
+        // with --normalize the shared scale is in normalized units
+        let norm_max = windows
+            .iter()
+            .zip(&self.samples)
+            .map(|((forward, reverse), sample)| {
+                let max = forward.iter().chain(reverse.iter().flatten()).max();
+                *max.unwrap_or(&0) as f64 * sample.factor()
+            })
+            .fold(0.0, f64::max);
+        self.samples
+            .iter()
+            .map(|sample| (norm_max / sample.factor()).round() as u64)
+            .collect()
     }
 }
 
+/// Count the data points of each depth in `bins` buckets of equal width from 0 to the highest
+/// depth, as `(lowest depth, count)`; fewer buckets are made when the depths don't span them
+pub fn depth_histogram(data: &[u64], bins: usize) -> Vec<(u64, u64)> {
+    let max = data.iter().copied().max().unwrap_or(0);
+    let width = (max + 1).div_ceil(bins.max(1) as u64);
+    let mut counts = vec![0; (max / width + 1) as usize];
+    for &depth in data {
+        counts[(depth / width) as usize] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| (i as u64 * width, count))
+        .collect()
+}
+
+/// Merge every `size` depths into one with the given statistic
+pub fn bin_depths(data: &[u64], size: usize, stat: cli::BinStat) -> Vec<u64> {
+    if size <= 1 {
+        return data.to_vec();
+    }
+    data.chunks(size)
+        .map(|bin| match stat {
+            cli::BinStat::mean => bin.iter().sum::<u64>() / bin.len() as u64,
+            cli::BinStat::max => *bin.iter().max().unwrap_or(&0),
+            cli::BinStat::min => *bin.iter().min().unwrap_or(&0),
+        })
+        .collect()
+}
+
 /// main function to run the tui
-pub fn tview(
-    data: Vec<u64>,
-    start: u32,
-    legend: String,
-    step: u8,
-    color: cli::Color,
-) -> Result<()> {
+pub fn tview(covs: Vec<RegionCov>, options: ViewOptions) -> Result<()> {
+    // resume where the last session left each region
+    let session = options.session.clone();
+    let mut positions = match &session {
+        Some(path) if path.exists() => load_session(path)?,
+        _ => BTreeMap::new(),
+    };
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
+    if options.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // get initial width
-    let init_width = terminal.size()?.width;
+    // a panic would leave the shell in raw mode, give it back before the message is printed
+    let mouse = options.mouse;
+    let previous_hook = Arc::new(panic::take_hook());
+    let hook = Arc::clone(&previous_hook);
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal(&mut io::stdout(), mouse);
+        hook(info);
+    }));
 
-    // parse color to crossterm color
-    let color = color.to_string().parse::<Color>()?;
+    // get initial width
+    let init_width = track_width(terminal.size()?.width);
 
     // create app and run it
-    let tick_rate = Duration::from_millis(250);
-    let app = CovView::new(data, legend, init_width, start);
-    let res = run_app(&mut terminal, app, tick_rate, step, color);
+    let redraw_on_event = options.redraw_on_event;
+    let tick_rate = if redraw_on_event {
+        IDLE_TICK
+    } else {
+        Duration::from_millis(options.tick_ms)
+    };
+    let step = options.step;
+    let mut app = App::new(covs, init_width, options);
+    for view in app.views.iter_mut() {
+        if let Some(&pos) = positions.get(&view.session_key()) {
+            if let Some(index) = view.index_of(pos) {
+                view.start_at(index, init_width);
+            }
+        }
+    }
+    let res = run_app(&mut terminal, &mut app, tick_rate, step, redraw_on_event);
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    // restore terminal, and the panic hook it no longer needs
+    let _ = panic::take_hook();
+    if let Ok(previous_hook) = Arc::try_unwrap(previous_hook) {
+        panic::set_hook(previous_hook);
+    }
+    restore_terminal(terminal.backend_mut(), mouse)?;
 
     if let Err(err) = res {
-        println!("{err:?}");
+        log::error!("{err:?}");
+    }
+    for path in &app.saved {
+        log::info!("saved {}", path.display());
     }
+    for locus in &app.copied {
+        log::info!("copied {}", locus);
+    }
+    if let Some(path) = session {
+        for view in &app.views {
+            positions.insert(view.session_key(), view.coord(view.view_start));
+        }
+        save_session(&path, &positions)?;
+    }
+
+    Ok(())
+}
+
+// leave raw mode and the alternate screen with the cursor shown, releasing the mouse if it
+// was captured
+fn restore_terminal<W: Write>(out: &mut W, mouse: bool) -> io::Result<()> {
+    disable_raw_mode()?;
+    if mouse {
+        execute!(out, DisableMouseCapture)?;
+    }
+    execute!(out, LeaveAlternateScreen, cursor::Show)
+}
+
+// the last viewed coordinate of each region, keyed by chr:start-end
+fn load_session(path: &Path) -> Result<BTreeMap<String, u32>> {
+    let file = File::open(path)?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| anyhow!("invalid session file {}: {}", path.display(), e))
+}
 
+fn save_session(path: &Path, positions: &BTreeMap<String, u32>) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, positions)?;
+    writeln!(writer)?;
+    writer.flush()?;
     Ok(())
 }
 
 // run the app
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
-    mut app: CovView,
+    app: &mut App,
     tick_rate: Duration,
     size: u8,
-    color: Color,
+    redraw_on_event: bool,
 ) -> Result<()> {
     let mut last_tick = Instant::now();
+    // whether anything may have changed since the last draw
+    let mut dirty = true;
     loop {
-        terminal.draw(|f| ui(f, &app, color))?;
+        if dirty || !redraw_on_event {
+            terminal.draw(|f| ui(f, app))?;
+            dirty = false;
+        }
         let timeout = tick_rate.saturating_sub(last_tick.elapsed());
         // get current width in loop
-        let curr_width = terminal.size()?.width;
+        let curr_width = track_width(terminal.size()?.width);
         if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Left => {
-                        // move view to left
-                        let dir_step = -(size as i32);
-                        app.move_view(dir_step, curr_width)
+            let event = event::read()?;
+            dirty = true;
+            // every region keeps its start and fills the new width
+            if let Event::Resize(width, _) = event {
+                for view in app.views.iter_mut() {
+                    view.resize(track_width(width));
+                }
+            }
+            // without --no-mouse, the wheel scrolls and dragging the tracks pans them
+            if let Event::Mouse(mouse) = event {
+                match mouse.kind {
+                    MouseEventKind::ScrollUp => {
+                        app.view_mut().move_view(-(size as i32), curr_width)
                     }
-                    KeyCode::Right => {
-                        // move view to right
-                        let dir_step = size as i32;
-                        app.move_view(dir_step, curr_width)
+                    MouseEventKind::ScrollDown => app.view_mut().move_view(size as i32, curr_width),
+                    MouseEventKind::Down(MouseButton::Left) if mouse.column >= AXIS_WIDTH => {
+                        app.drag_column = Some(mouse.column)
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) => {
+                        if let Some(last) = app.drag_column {
+                            // the tracks follow the pointer, a column per column
+                            let columns = last as i32 - mouse.column as i32;
+                            app.view_mut().move_view(columns, curr_width);
+                            app.drag_column = Some(mouse.column);
+                        }
+                    }
+                    MouseEventKind::Up(MouseButton::Left) => app.drag_column = None,
+                    MouseEventKind::Moved => {
+                        app.view_mut().hover_column = mouse.column.checked_sub(AXIS_WIDTH)
                     }
                     _ => {}
                 }
             }
+            if let Event::Key(key) = event {
+                app.message = None;
+                if app.show_keys {
+                    // any key closes the help popup
+                    app.show_keys = false;
+                } else if let Some(prompt) = app.prompt.as_mut() {
+                    // typing a position to go to
+                    match key.code {
+                        KeyCode::Char(c) if c.is_ascii_digit() || c == ',' => prompt.push(c),
+                        KeyCode::Backspace => {
+                            prompt.pop();
+                        }
+                        KeyCode::Enter => app.submit_prompt(curr_width),
+                        KeyCode::Esc => app.prompt = None,
+                        _ => {}
+                    }
+                } else {
+                    match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Left | KeyCode::Right
+                            if key.modifiers.contains(KeyModifiers::SHIFT) =>
+                        {
+                            // page jump by a whole window
+                            let page = curr_width as i32;
+                            let dir_step = if key.code == KeyCode::Left {
+                                -page
+                            } else {
+                                page
+                            };
+                            app.view_mut().move_view(dir_step, curr_width)
+                        }
+                        KeyCode::PageUp | KeyCode::Char('b') => {
+                            app.view_mut().move_view(-(curr_width as i32), curr_width)
+                        }
+                        KeyCode::PageDown | KeyCode::Char('f') => {
+                            app.view_mut().move_view(curr_width as i32, curr_width)
+                        }
+                        KeyCode::Char('L') => app.toggle_log(),
+                        KeyCode::Char('N') => app.view_mut().toggle_display(),
+                        KeyCode::Left | KeyCode::Char('h') if app.view().cursor_pos.is_some() => {
+                            app.view_mut().move_cursor(-1, curr_width)
+                        }
+                        KeyCode::Right | KeyCode::Char('l') if app.view().cursor_pos.is_some() => {
+                            app.view_mut().move_cursor(1, curr_width)
+                        }
+                        KeyCode::Char('c') => app.view_mut().toggle_cursor(),
+                        KeyCode::Char('s') => app.show_stats = !app.show_stats,
+                        KeyCode::Char('v') => app.show_variants = !app.show_variants,
+                        KeyCode::Char('m') => app.show_minimap = !app.show_minimap,
+                        KeyCode::Char('d') => app.show_hist = !app.show_hist,
+                        KeyCode::Char('H') => app.show_help = !app.show_help,
+                        KeyCode::Char('?') => app.show_keys = true,
+                        KeyCode::Up | KeyCode::Char('k') => app.view_mut().step_min_cov(true),
+                        KeyCode::Down | KeyCode::Char('j') => app.view_mut().step_min_cov(false),
+                        KeyCode::Char('W') => {
+                            // no wider than the region
+                            app.smooth = (app.smooth * 2 + 1).min(app.view().data_len() | 1)
+                        }
+                        KeyCode::Char('w') => app.smooth = (app.smooth / 2) | 1,
+                        KeyCode::Char('U') => app.step_usable_mapq(true),
+                        KeyCode::Char('u') => app.step_usable_mapq(false),
+                        KeyCode::Char('D') => app.filters.dups = !app.filters.dups,
+                        KeyCode::Char('P') => app.filters.proper_pairs = !app.filters.proper_pairs,
+                        KeyCode::Char('M') => app.toggle_mapq_filter(),
+                        KeyCode::Char('{') => app.lower_max_depth(),
+                        KeyCode::Char('}') => app.raise_max_depth(),
+                        KeyCode::Char(']') => app.jump_gap(true, curr_width),
+                        KeyCode::Char('[') => app.jump_gap(false, curr_width),
+                        KeyCode::Left | KeyCode::Char('h') => {
+                            // move view to left
+                            let dir_step = -(size as i32);
+                            app.view_mut().move_view(dir_step, curr_width)
+                        }
+                        KeyCode::Right | KeyCode::Char('l') => {
+                            // move view to right
+                            let dir_step = size as i32;
+                            app.view_mut().move_view(dir_step, curr_width)
+                        }
+                        KeyCode::Char('0') => app.view_mut().jump_start(curr_width),
+                        KeyCode::Char('$') => app.view_mut().jump_end(curr_width),
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            app.view_mut().zoom_in(curr_width)
+                        }
+                        KeyCode::Char('-') => app.view_mut().zoom_out(curr_width),
+                        KeyCode::Tab | KeyCode::Char('n') => app.next_region(),
+                        KeyCode::BackTab | KeyCode::Char('p') => app.prev_region(),
+                        KeyCode::Char('g') => app.prompt = Some(String::new()),
+                        KeyCode::Char('e') => {
+                            if let Err(err) = app.export_svg() {
+                                app.message = Some(format!("export failed: {}", err));
+                            }
+                        }
+                        KeyCode::Char('y') => {
+                            if let Err(err) = app.copy_locus() {
+                                app.message = Some(format!("copy failed: {}", err));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                // a switched filter, or a region shown since, is counted again
+                app.sync_filters();
+            }
         }
         if last_tick.elapsed() >= tick_rate {
             last_tick = Instant::now();
@@ -155,59 +1656,923 @@ fn run_app<B: Backend>(
 }
 
 // draw the ui
-fn ui(f: &mut Frame, app: &CovView, color: Color) {
+fn ui(f: &mut Frame, app: &App) {
+    let total = app.views.len();
+    let index = app.current + 1;
+    let view = app.view();
+
     // get full size and split it to chunks
     let full = f.size();
     let width = full.width;
+
+    // too narrow to fit the coordinate labels
+    if width < MIN_WIDTH + AXIS_WIDTH {
+        let warning = Paragraph::new("window too narrow")
+            .style(Style::default().fg(app.label_color))
+            .wrap(Wrap { trim: true });
+        f.render_widget(warning, full);
+        return;
+    }
+    // the prompt and errors show even with the help hidden
+    let help_rows = u16::from(app.show_help || app.prompt.is_some() || app.message.is_some());
+    let mut constraints = vec![
+        Constraint::Min(0),            // the rest for the tracks
+        Constraint::Length(1),         // one row for the label
+        Constraint::Length(help_rows), // one row for the help text, unless hidden
+    ];
+    if app.show_stats {
+        // bordered lines of statistics under the label, one more for the low runs,
+        // for each input file
+        let per_sample = if view.min_cov.is_some() { 2 } else { 1 };
+        let lines = 2 + per_sample * view.samples.len() as u16;
+        constraints.insert(2, Constraint::Length(lines));
+    }
+    if app.show_minimap {
+        // one row above the help text
+        constraints.insert(constraints.len() - 1, Constraint::Length(1));
+    }
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         // .margin(1)
-        .constraints(
-            [
-                Constraint::Percentage(96), // 96% for sparkline
-                Constraint::Percentage(2),  // 2% for label
-                Constraint::Percentage(2),  // 2% for help text
-            ]
-            .as_ref(),
-        )
+        .constraints(constraints)
         .split(full);
+    let help_area = chunks[chunks.len() - 1];
+    let minimap_area = app.show_minimap.then(|| chunks[chunks.len() - 2]);
 
     // re-generate legend
-    let curr_max = app.data[app.view_start as usize..app.view_end as usize]
+    let window = view.view_start as usize..view.view_end as usize;
+    let max_texts: Vec<String> = view
+        .samples
         .iter()
-        .max()
-        .unwrap_or(&0);
-    let legend = format!("{} (current max: {})", app.legend, curr_max);
-
-    let sparkline = Sparkline::default()
-        .block(
-            Block::new()
-                .title(legend)
-                .title_alignment(Alignment::Center),
-        )
-        .data(&app.data[app.view_start as usize..app.view_end as usize])
-        .style(Style::default().fg(color));
+        .map(|sample| {
+            let curr_max = *sample.data[window.clone()].iter().max().unwrap_or(&0);
+            let max_text = match &sample.reverse {
+                Some(reverse) => format!(
+                    "current max: {} forward / {} reverse",
+                    sample.format_depth(curr_max),
+                    sample.format_depth(*reverse[window.clone()].iter().max().unwrap_or(&0))
+                ),
+                None => format!("current max: {}", sample.format_depth(curr_max)),
+            };
+            let max_text = match app.scale {
+                Scale::Linear => max_text,
+                Scale::Log => format!("{}, log2 scale", max_text),
+            };
+            let max_text = match view.display {
+                DepthDisplay::Depth => max_text,
+                DepthDisplay::WindowPercent => format!("{}, % of window max", max_text),
+            };
+            let max_text = match app.smooth {
+                1 => max_text,
+                smooth => format!("{}, {}-point average", max_text, smooth),
+            };
+            let max_text = match (app.usable_step, &sample.usable) {
+                (Some(step), Some(_)) => {
+                    format!("{}, MAPQ ≥{} shaded", max_text, MAPQ_STEPS[step])
+                }
+                _ => max_text,
+            };
+            match sample.breadth {
+                Some((min_depth, fraction)) => format!(
+                    "{}, breadth ≥{}x = {:.1}%",
+                    max_text,
+                    min_depth,
+                    fraction * 100.0
+                ),
+                None => max_text,
+            }
+        })
+        .collect();
+    // several input files carry their max in their own title
+    let multi = view.samples.len() > 1;
+    let legend = match (total > 1, multi) {
+        (true, false) => format!("[{}/{}] {} ({})", index, total, view.legend, max_texts[0]),
+        (false, false) => format!("{} ({})", view.legend, max_texts[0]),
+        (true, true) => format!("[{}/{}] {}", index, total, view.legend),
+        (false, true) => view.legend.clone(),
+    };
+    // how the depths on screen were counted
+    let mut filters = Vec::new();
+    if view.filters.dups {
+        filters.push("duplicates excluded".to_string());
+    }
+    if view.filters.proper_pairs {
+        filters.push("proper pairs only".to_string());
+    }
+    if view.filters.mapq {
+        filters.push(format!("MAPQ ≥{}", app.min_mapq));
+    }
+    if app.weighted {
+        filters.push("MAPQ-weighted".to_string());
+    }
+    let legend = if filters.is_empty() {
+        legend
+    } else {
+        format!("{} [{}]", legend, filters.join(", "))
+    };
+    let legend = match (app.max_depth, view.display) {
+        (Some(cap), DepthDisplay::Depth) => format!("{} [clipped at {}x]", legend, cap),
+        _ => legend,
+    };
+    let legend = if app.show_hist {
+        format!("{} [depth histogram of the region]", legend)
+    } else {
+        legend
+    };
+    let block = Block::new()
+        .title(legend)
+        .title_alignment(Alignment::Center);
+    let mut area = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
 
-    f.render_widget(sparkline, chunks[0]);
+    // the gc track takes the bottom rows, under all input files
+    let mut gc_area = None;
+    if view.gc.is_some() && !app.show_hist {
+        let [tracks_area, gc_block_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(GC_HEIGHT + 1)]).areas(area);
+        area = tracks_area;
+        gc_area = Some(gc_block_area);
+    }
+    // feature bars go above the gc track
+    let mut feature_area = None;
+    if view.features.is_some() && !app.show_hist {
+        let [tracks_area, features] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+        area = tracks_area;
+        feature_area = Some(features);
+    }
+    // variant markers sit right under the tracks, above the features
+    let mut marker_area = None;
+    if view.variants.is_some() && app.show_variants && !app.show_hist {
+        let [tracks_area, markers] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+        area = tracks_area;
+        marker_area = Some(markers);
+    }
+
+    // the depths on screen, all input files share a scale so they compare
+    let windows = view.sample_windows(app.bin_stat, app.smooth);
+    let maxes = app.drawn_maxes(&windows);
+
+    // one row of tracks per input file, stacked
+    let n_samples = windows.len().max(1) as u32;
+    let rows =
+        Layout::vertical(vec![Constraint::Ratio(1, n_samples); n_samples as usize]).split(area);
+    let mut track_areas = Vec::with_capacity(windows.len());
+    for (i, (forward, reverse)) in windows.iter().enumerate() {
+        let mut row = rows[i];
+        if multi {
+            let title = format!("{} ({})", view.samples[i].name, max_texts[i]);
+            let sample_block = Block::new().title(title);
+            row = sample_block.inner(rows[i]);
+            f.render_widget(sample_block, rows[i]);
+        }
+        if app.show_hist {
+            render_histogram(f, app, row, i);
+            continue;
+        }
+        // the soft-clip track goes at the bottom, under the mapq track
+        let mut softclip_area = None;
+        if view.samples[i].softclips.is_some() {
+            let [depth_row, softclip_row] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(SOFTCLIP_HEIGHT + 1)])
+                    .areas(row);
+            row = depth_row;
+            softclip_area = Some(softclip_row);
+        }
+        // the ratio to the baseline goes above the soft clips
+        let mut ratio_area = None;
+        if view.baseline.is_some() {
+            let [depth_row, ratio_row] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(RATIO_HEIGHT + 1)])
+                    .areas(row);
+            row = depth_row;
+            ratio_area = Some(ratio_row);
+        }
+        let mut mapq_area = None;
+        if view.samples[i].mapq.is_some() {
+            let [depth_row, mapq_row] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(MAPQ_HEIGHT + 1)])
+                    .areas(row);
+            row = depth_row;
+            mapq_area = Some(mapq_row);
+        }
+        // variant hints sit right under the depths
+        let mut hint_area = None;
+        if view.samples[i].hints.is_some() {
+            let [depth_row, hint_row] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(row);
+            row = depth_row;
+            hint_area = Some(hint_row);
+        }
+        let track_area = render_sample(f, app, row, forward, reverse.as_deref(), maxes[i], i);
+        track_areas.push(track_area);
+        if let (Some(mapq), Some(mapq_area)) = (&view.samples[i].mapq, mapq_area) {
+            let color = app.colors[(windows.len() + i) % app.colors.len()];
+            let mapq_block = Block::new()
+                .title("MAPQ")
+                .title_style(Style::default().fg(color));
+            let mapq_row = mapq_block.inner(mapq_area);
+            f.render_widget(mapq_block, mapq_area);
+            let [axis_area, mapq_track_area] =
+                Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                    .areas(mapq_row);
+            let mapq_window = view.window(mapq, cli::BinStat::mean);
+            let max = mapq_window.iter().copied().max().unwrap_or(0).max(MAPQ_MAX);
+            let mapq_track = Track::new(&mapq_window)
+                .max(max)
+                .style(Style::default().fg(color));
+            f.render_widget(mapq_track, mapq_track_area);
+            let mapq_axis = YAxis::new(max).style(Style::default().fg(app.label_color));
+            f.render_widget(mapq_axis, axis_area);
+            track_areas.push(mapq_track_area);
+        }
+        if let (Some(hints), Some(hint_area)) = (&view.samples[i].hints, hint_area) {
+            let [_, hint_area] =
+                Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                    .areas(hint_area);
+            let hint_window = view.window(hints, cli::BinStat::max);
+            for (column, &percent) in hint_window.iter().enumerate() {
+                if percent < HINT_MIN || column as u16 >= hint_area.width {
+                    continue;
+                }
+                let color = if percent >= HINT_STRONG {
+                    Color::LightRed
+                } else {
+                    Color::Yellow
+                };
+                f.buffer_mut()
+                    .get_mut(hint_area.x + column as u16, hint_area.y)
+                    .set_symbol("▪")
+                    .set_style(Style::default().fg(color));
+            }
+        }
+        if let (Some(baseline), Some(ratio_area)) = (&view.baseline, ratio_area) {
+            let ratio_block = Block::new().title(Line::from(vec![
+                Span::styled("log2 ratio: ", Style::default().fg(app.label_color)),
+                Span::styled("gain", Style::default().fg(GAIN_COLOR)),
+                Span::styled(" / ", Style::default().fg(app.label_color)),
+                Span::styled("loss", Style::default().fg(LOSS_COLOR)),
+            ]));
+            let ratio_row = ratio_block.inner(ratio_area);
+            f.render_widget(ratio_block, ratio_area);
+            let [axis_area, ratio_track_area] =
+                Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                    .areas(ratio_row);
+            let [gain_area, loss_area] =
+                Layout::vertical([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+                    .areas(ratio_track_area);
+            // bars grow both ways from zero, in hundredths of a log2 step
+            let ratios = view.ratio_window(&view.samples[i], baseline);
+            let hundredths = |ratio: f64| (ratio * 100.0).round() as u64;
+            let gains: Vec<u64> = ratios
+                .iter()
+                .map(|ratio| ratio.filter(|r| *r > 0.0).map_or(0, hundredths))
+                .collect();
+            let losses: Vec<u64> = ratios
+                .iter()
+                .map(|ratio| ratio.filter(|r| *r < 0.0).map_or(0, |r| hundredths(-r)))
+                .collect();
+            let max = hundredths(RATIO_MAX);
+            let gain_track = Track::new(&gains)
+                .max(max)
+                .style(Style::default().fg(GAIN_COLOR));
+            f.render_widget(gain_track, gain_area);
+            let loss_track = Track::new(&losses)
+                .max(max)
+                .orientation(Orientation::Down)
+                .style(Style::default().fg(LOSS_COLOR));
+            f.render_widget(loss_track, loss_area);
+            // the axis only marks the clipped ends and zero between them
+            let digits = AXIS_WIDTH as usize - 1;
+            for y in axis_area.top()..axis_area.bottom() {
+                let text = if y == axis_area.top() {
+                    format!("{:>digits$}┤", format!("+{}", RATIO_MAX))
+                } else if y == axis_area.bottom() - 1 {
+                    format!("{:>digits$}┤", format!("-{}", RATIO_MAX))
+                } else if y == loss_area.top() {
+                    format!("{:>digits$}┤", 0)
+                } else {
+                    format!("{:>digits$}│", "")
+                };
+                f.buffer_mut().set_stringn(
+                    axis_area.x,
+                    y,
+                    text,
+                    axis_area.width as usize,
+                    Style::default().fg(app.label_color),
+                );
+            }
+            track_areas.push(ratio_track_area);
+        }
+        if let (Some(clips), Some(softclip_area)) = (&view.samples[i].softclips, softclip_area) {
+            let softclip_block = Block::new()
+                .title("Soft clips")
+                .title_style(Style::default().fg(SOFTCLIP_COLOR));
+            let softclip_row = softclip_block.inner(softclip_area);
+            f.render_widget(softclip_block, softclip_area);
+            let [axis_area, softclip_track_area] =
+                Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                    .areas(softclip_row);
+            let clips_window = view.window(clips, cli::BinStat::max);
+            let max = clips_window.iter().copied().max().unwrap_or(0);
+            let softclip_track = Track::new(&clips_window)
+                .max(max)
+                .style(Style::default().fg(SOFTCLIP_COLOR));
+            f.render_widget(softclip_track, softclip_track_area);
+            let softclip_axis = YAxis::new(max).style(Style::default().fg(app.label_color));
+            f.render_widget(softclip_axis, axis_area);
+            track_areas.push(softclip_track_area);
+        }
+    }
+    if let (Some(variants), Some(marker_area)) = (&view.variants, marker_area) {
+        let [_, marker_area] =
+            Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                .areas(marker_area);
+        for variant in variants {
+            let Some(column) = view.column_of(variant.pos) else {
+                continue;
+            };
+            if column >= marker_area.width {
+                continue;
+            }
+            let color = match variant.kind {
+                VariantKind::Snv => Color::LightRed,
+                VariantKind::Indel => Color::LightMagenta,
+                VariantKind::Other => Color::White,
+            };
+            f.buffer_mut()
+                .get_mut(marker_area.x + column, marker_area.y)
+                .set_symbol(VARIANT_MARKER)
+                .set_style(Style::default().fg(color));
+        }
+    }
+    if let (Some(features), Some(feature_area)) = (&view.features, feature_area) {
+        let [_, feature_area] =
+            Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                .areas(feature_area);
+        let buf = f.buffer_mut();
+        // visible columns of each feature, thin lines first so exons stay on top
+        let spans: Vec<(&Feature, u16, u16)> = features
+            .iter()
+            .filter_map(|feature| {
+                let (first, last) = view.column_span(feature.start, feature.end)?;
+                let last = last.min(feature_area.width.saturating_sub(1));
+                (first <= last).then_some((feature, first, last))
+            })
+            .collect();
+        for thick in [false, true] {
+            for &(_, first, last) in spans.iter().filter(|(f, _, _)| f.thick == thick) {
+                let symbol = if thick { "█" } else { "─" };
+                buf.set_string(
+                    feature_area.x + first,
+                    feature_area.y,
+                    symbol.repeat((last - first + 1) as usize),
+                    Style::default().fg(FEATURE_COLOR),
+                );
+            }
+        }
+        // a name goes in the middle of its bar when it fits with a cell to spare
+        for &(feature, first, last) in &spans {
+            let Some(name) = &feature.name else {
+                continue;
+            };
+            let span = last - first + 1;
+            let len = name.chars().count() as u16;
+            if len + 2 <= span {
+                let style = if feature.thick {
+                    Style::default().fg(Color::Black).bg(FEATURE_COLOR)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                buf.set_string(
+                    feature_area.x + first + (span - len) / 2,
+                    feature_area.y,
+                    name,
+                    style,
+                );
+            }
+        }
+    }
+    if let (Some(gc), Some(gc_block_area)) = (&view.gc, gc_area) {
+        let gc_block = Block::new()
+            .title("GC%")
+            .title_style(Style::default().fg(GC_COLOR));
+        let gc_row = gc_block.inner(gc_block_area);
+        f.render_widget(gc_block, gc_block_area);
+        let [axis_area, track_area] =
+            Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)]).areas(gc_row);
+        // percentages are always drawn against the full 100
+        let gc_window = view.window(gc, cli::BinStat::mean);
+        let gc_track = Track::new(&gc_window)
+            .max(100)
+            .style(Style::default().fg(GC_COLOR));
+        f.render_widget(gc_track, track_area);
+        let gc_axis = YAxis::new(100).style(Style::default().fg(app.label_color));
+        f.render_widget(gc_axis, axis_area);
+        track_areas.push(track_area);
+    }
+
+    // highlight the column under the cursor
+    if let Some(cursor) = view.cursor_pos {
+        if (view.view_start..view.view_end).contains(&cursor) {
+            let highlight = Style::default().fg(Color::White).bg(Color::DarkGray);
+            for track_area in track_areas {
+                let x = track_area.x + ((cursor - view.view_start) / view.zoom) as u16;
+                if x < track_area.right() {
+                    for y in track_area.top()..track_area.bottom() {
+                        f.buffer_mut().get_mut(x, y).set_style(highlight);
+                    }
+                }
+            }
+        }
+    }
+
+    // coordinate labels line up with the tracks, not the axis
+    let [_, label_area] =
+        Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)]).areas(chunks[1]);
+    let (label_start, label_end) = view.window_labels(app.coord_base);
+    // the cursor, or else the column under the mouse, is inspected in the middle of the row
+    let inspected = view.cursor_pos.or_else(|| view.hover_pos());
+    let fmt_label = generate_and_format_dynamic_label(
+        label_start,
+        label_end,
+        label_area.width,
+        inspected.is_none(),
+    );
+
+    let label_paragraph = Paragraph::new(fmt_label).style(Style::default().fg(app.label_color));
+    f.render_widget(label_paragraph, label_area);
+
+    // exact depth under the cursor or the pointer, between the start and end labels
+    if let Some(cursor) = inspected {
+        let first = view.coord(cursor) + app.coord_base;
+        let pos = match view.bin_size {
+            1 => first.to_string(),
+            bin_size => format!("{}-{}", first, first + bin_size - 1),
+        };
+        let depths: Vec<String> = view
+            .samples
+            .iter()
+            .map(|sample| {
+                let depth = sample.format_depth(sample.data[cursor as usize]);
+                let depth = match &sample.reverse {
+                    Some(reverse) => format!(
+                        "{} forward / {} reverse",
+                        depth,
+                        sample.format_depth(reverse[cursor as usize])
+                    ),
+                    None => depth,
+                };
+                let depth = match &sample.weighted {
+                    Some(weighted) => {
+                        format!("{}, {:.2} weighted", depth, weighted[cursor as usize])
+                    }
+                    None => depth,
+                };
+                let depth = match &sample.mapq {
+                    Some(mapq) => format!("{} (MAPQ {})", depth, mapq[cursor as usize]),
+                    None => depth,
+                };
+                let depth = match (app.usable_step, &sample.usable) {
+                    (Some(step), Some(usable)) => format!(
+                        "{}, {} at MAPQ ≥{}",
+                        depth,
+                        sample.format_depth(usable[step][cursor as usize]),
+                        MAPQ_STEPS[step]
+                    ),
+                    _ => depth,
+                };
+                let depth = match &sample.hints {
+                    Some(hints) => format!("{}, {}% disagree", depth, hints[cursor as usize]),
+                    None => depth,
+                };
+                let depth = match &sample.softclips {
+                    Some(clips) => format!("{}, {} clipped", depth, clips[cursor as usize]),
+                    None => depth,
+                };
+                let depth = match view
+                    .baseline
+                    .as_ref()
+                    .map(|baseline| view.log2_ratio(sample, baseline, cursor, cursor + 1))
+                {
+                    Some(Some(ratio)) => format!("{}, log2 ratio {:+.2}", depth, ratio),
+                    Some(None) => format!("{}, no baseline", depth),
+                    None => depth,
+                };
+                if multi {
+                    format!("{} {}", depth, sample.name)
+                } else {
+                    depth
+                }
+            })
+            .collect();
+        let mut cursor_text = format!("{}:{} = {}", view.chrom, pos, depths.join(", "));
+        if let Some(gc) = &view.gc {
+            cursor_text.push_str(&format!(", GC {}%", gc[cursor as usize]));
+        }
+        if let Some(n_percent) = &view.n_percent {
+            if n_percent[cursor as usize] >= N_MASK_MIN {
+                cursor_text.push_str(", reference N");
+            }
+        }
+        let cursor_paragraph = Paragraph::new(cursor_text)
+            .style(Style::default().fg(Color::White))
+            .alignment(Alignment::Center);
+        f.render_widget(cursor_paragraph, label_area);
+    }
 
-    let label_end = app.label_start + width as u32;
-    let fmt_label = generate_and_format_dynamic_label(app.label_start, label_end, chunks[1].width);
+    // the whole region as a line, with the window on screen highlighted
+    if let Some(minimap_area) = minimap_area {
+        let [_, minimap_area] =
+            Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)])
+                .areas(minimap_area);
+        let buf = f.buffer_mut();
+        buf.set_string(
+            minimap_area.x,
+            minimap_area.y,
+            "─".repeat(minimap_area.width as usize),
+            Style::default().fg(Color::DarkGray),
+        );
+        if let Some((first, last)) = minimap_span(
+            view.view_start,
+            view.view_end,
+            view.data_len(),
+            minimap_area.width,
+        ) {
+            buf.set_string(
+                minimap_area.x + first,
+                minimap_area.y,
+                "━".repeat((last - first) as usize),
+                Style::default().fg(MINIMAP_COLOR),
+            );
+        }
+    }
 
-    let label_paragraph = Paragraph::new(fmt_label).style(Style::default().fg(Color::Cyan));
-    f.render_widget(label_paragraph, chunks[1]);
+    if app.show_stats {
+        let stats_block = Block::new()
+            .borders(Borders::ALL)
+            .title("Stats")
+            .border_style(Style::default().fg(app.label_color));
+        let mut lines = Vec::new();
+        for sample in &view.samples {
+            let stats = format_stats(&sample.stats, sample.norm);
+            // the reads behind the depths, none hints at filters that leave nothing
+            let stats = match sample.reads {
+                Some(reads) => format!("reads: {}  {}", reads, stats),
+                None => stats,
+            };
+            lines.push(Line::from(if multi {
+                format!("{}: {}", sample.name, stats)
+            } else {
+                stats
+            }));
+            if let Some(min_cov) = view.min_cov {
+                lines.push(
+                    Line::from(format_low_runs(
+                        &view.chrom,
+                        sample,
+                        min_cov,
+                        app.coord_base,
+                    ))
+                    .red(),
+                );
+            }
+        }
+        let stats_paragraph = Paragraph::new(lines)
+            .block(stats_block)
+            .alignment(Alignment::Center);
+        f.render_widget(stats_paragraph, chunks[2]);
+    }
 
-    let help_text = "Press ◄ ► to scroll, 'q' to quit";
+    let switch_text = if total > 1 {
+        ", n/p or Tab/Shift+Tab to switch region"
+    } else {
+        ""
+    };
+    let gap_text = match view.min_cov {
+        Some(min_cov) => format!(", ▲ ▼ to change min cov ({}x), [/] for gaps", min_cov),
+        None => ", ▲ ▼ to set min cov".to_string(),
+    };
+    let variant_text = if view.variants.is_some() {
+        ", 'v' for variants"
+    } else {
+        ""
+    };
+    let (help_text, help_color) = match (&app.prompt, &app.message) {
+        (Some(prompt), _) => (
+            format!("Go to position: {}_ (Enter to jump, Esc to cancel)", prompt),
+            app.label_color,
+        ),
+        (None, Some(message)) => (message.clone(), Color::Red),
+        (None, None) => (
+            format!(
+                "Press ◄ ►/h l to scroll, +/- to zoom ({} bp/col), 'c' for cursor, 's' for stats, 'm' for minimap, 'L' for log scale, {{/}} to clip spikes, 'g' to go to, 'e' to export svg{}{}{}, '?' for all keys, 'q' to quit",
+                view.zoom * view.bin_size, gap_text, variant_text, switch_text
+            ),
+            app.label_color,
+        ),
+    };
     let help_paragraph = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::Cyan))
+        .style(Style::default().fg(help_color))
         .alignment(Alignment::Center);
-    f.render_widget(help_paragraph, chunks[2]);
+    f.render_widget(help_paragraph, help_area);
+
+    if app.show_keys {
+        render_keys(f, full, app.label_color);
+    }
 }
 
-// generate dynamic label
-fn generate_and_format_dynamic_label(label_start: u32, label_end: u32, axis_width: u16) -> String {
-    let start_label = format!("{:09}", label_start);
-    let end_label = format!("{:09}", label_end);
-    // compute the space between start and end
-    let space = " ".repeat((axis_width - 18) as usize);
-    format!("{}{}{}", start_label, space, end_label)
+// draw the popup listing every key in the middle of the screen, over the tracks
+fn render_keys(f: &mut Frame, area: Rect, color: Color) {
+    let key_width = KEYS
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = KEYS
+        .iter()
+        .map(|(key, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:>key_width$}  ", key),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+    let text_width = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+    // two more columns and rows for the border, cut to the screen
+    let width = (text_width + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Keys (any key to close) ")
+        .style(Style::default().fg(color));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+// the number of data points of each depth over the whole region of a file, both strands
+// together, with the lowest depth of each bar under it
+fn render_histogram(f: &mut Frame, app: &App, area: Rect, index: usize) {
+    let sample = &app.view().samples[index];
+    let depths: Vec<u64> = match &sample.reverse {
+        Some(reverse) => sample
+            .data
+            .iter()
+            .zip(reverse)
+            .map(|(f, r)| f + r)
+            .collect(),
+        None => sample.data.clone(),
+    };
+    let fit = area.width / (HIST_BAR_WIDTH + 1);
+    let bins = app.hist_bins.unwrap_or(u32::from(fit)).max(1);
+    let histogram = depth_histogram(&depths, bins as usize);
+    // a bar and a column of gap each, narrower bars when more were asked for than fit
+    let bar_width = (area.width / histogram.len().max(1) as u16)
+        .saturating_sub(1)
+        .clamp(1, HIST_BAR_WIDTH);
+    let bars: Vec<Bar> = histogram
+        .iter()
+        .map(|&(depth, count)| {
+            Bar::default()
+                .value(count)
+                .label(Line::from(depth.to_string()))
+        })
+        .collect();
+    let color = app.colors[index % app.colors.len()];
+    let chart = BarChart::default()
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(color))
+        .value_style(Style::default().fg(Color::Black).bg(color))
+        .label_style(Style::default().fg(app.label_color));
+    f.render_widget(chart, area);
+}
+
+// draw the tracks and y-axis of one input file, returning where the tracks went
+fn render_sample(
+    f: &mut Frame,
+    app: &App,
+    area: Rect,
+    forward: &[u64],
+    reverse: Option<&[u64]>,
+    max: u64,
+    index: usize,
+) -> Rect {
+    // the y-axis sits left of the tracks
+    let [axis_area, track_area] =
+        Layout::horizontal([Constraint::Length(AXIS_WIDTH), Constraint::Min(0)]).areas(area);
+    let axis_style = Style::default().fg(app.label_color);
+    let color = app.colors[index % app.colors.len()];
+    // as a percentage the full height is 100, whatever depth it stands for
+    let axis_max = match app.view().display {
+        DepthDisplay::Depth => max,
+        DepthDisplay::WindowPercent => 100,
+    };
+    // nothing is below a threshold of 0, so no --min-cov draws no red
+    let min_cov = app.view().min_cov.map_or(0, u64::from);
+    let low_style = Style::default().fg(Color::Red);
+    // the usable depth is counted on the reads of the forward or unstranded track
+    let usable = app.usable_window(&app.view().samples[index]);
+    let usable_style = Style::default().fg(USABLE_COLOR);
+    // a column with any mostly N data point is masked
+    let masked: Option<Vec<bool>> = app.view().n_percent.as_ref().map(|n_percent| {
+        app.view()
+            .window(n_percent, cli::BinStat::max)
+            .into_iter()
+            .map(|percent| percent >= N_MASK_MIN)
+            .collect()
+    });
+    let mask_style = Style::default().fg(MASK_COLOR);
+
+    match reverse {
+        Some(reverse) => {
+            // forward strand stands above the reverse strand, on the same scale
+            let halves = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]);
+            let [forward_axis, reverse_axis] = halves.areas(axis_area);
+            let [forward_area, reverse_area] = halves.areas(track_area);
+            let forward_track = Track::new(forward)
+                .highlight_below(min_cov, low_style)
+                .mask(masked.as_deref(), mask_style)
+                .shade(usable.as_deref(), usable_style)
+                .gradient(app.gradient)
+                .braille(app.braille)
+                .max(max)
+                .scale(app.scale)
+                .style(Style::default().fg(color));
+            f.render_widget(forward_track, forward_area);
+            let forward_axis_widget = YAxis::new(axis_max).style(axis_style).scale(app.scale);
+            f.render_widget(forward_axis_widget, forward_axis);
+            let reverse_track = Track::new(reverse)
+                .highlight_below(min_cov, low_style)
+                .mask(masked.as_deref(), mask_style)
+                .gradient(app.gradient)
+                .braille(app.braille)
+                .max(max)
+                .scale(app.scale)
+                .style(Style::default().fg(app.reverse_color))
+                .orientation(Orientation::Down);
+            f.render_widget(reverse_track, reverse_area);
+            let reverse_axis_widget = YAxis::new(axis_max)
+                .style(axis_style)
+                .orientation(Orientation::Down)
+                .scale(app.scale);
+            f.render_widget(reverse_axis_widget, reverse_axis);
+        }
+        None => {
+            let track = Track::new(forward)
+                .highlight_below(min_cov, low_style)
+                .mask(masked.as_deref(), mask_style)
+                .shade(usable.as_deref(), usable_style)
+                .gradient(app.gradient)
+                .braille(app.braille)
+                .max(max)
+                .scale(app.scale)
+                .style(Style::default().fg(color));
+            f.render_widget(track, track_area);
+            let axis = YAxis::new(axis_max).style(axis_style).scale(app.scale);
+            f.render_widget(axis, axis_area);
+        }
+    }
+    track_area
+}
+
+// one line summary of the region depths
+fn format_stats(stats: &DepthStats, norm: Option<(f64, &str)>) -> String {
+    let breadth: Vec<String> = THRESHOLDS
+        .iter()
+        .zip(stats.breadth)
+        .map(|(t, b)| format!(">={}x: {:.1}%", t, b * 100.0))
+        .collect();
+    match norm {
+        // breadth stays in raw depth, the thresholds are meant for it
+        Some((factor, unit)) => format!(
+            "{}: mean: {:.3}  median: {:.3}  min: {:.3}  max: {:.3}  sd: {:.3}  {}",
+            unit,
+            stats.mean * factor,
+            stats.median * factor,
+            stats.min as f64 * factor,
+            stats.max as f64 * factor,
+            stats.std_dev * factor,
+            breadth.join("  ")
+        ),
+        None => format!(
+            "mean: {:.1}  median: {:.1}  min: {}  max: {}  sd: {:.1}  {}",
+            stats.mean,
+            stats.median,
+            stats.min,
+            stats.max,
+            stats.std_dev,
+            breadth.join("  ")
+        ),
+    }
+}
+
+// count and coordinates of the runs below the coverage threshold
+fn format_low_runs(chrom: &str, sample: &SampleCov, min_cov: u32, coord_base: u32) -> String {
+    let runs: Vec<String> = sample
+        .low_runs
+        .iter()
+        .map(|(start, end)| format!("{}:{}-{}", chrom, start + coord_base, end))
+        .collect();
+    match runs.len() {
+        0 => format!("no positions below {}x", min_cov),
+        1 => format!("1 run below {}x: {}", min_cov, runs[0]),
+        n => format!("{} runs below {}x: {}", n, min_cov, runs.join(", ")),
+    }
+}
+
+// first and exclusive last minimap columns of the window `start..end` out of `len` data points,
+// at least one column wide so the window never disappears
+fn minimap_span(start: u32, end: u32, len: u32, width: u16) -> Option<(u16, u16)> {
+    if len == 0 || width == 0 {
+        return None;
+    }
+    let width = u64::from(width);
+    let first = (u64::from(start) * width / u64::from(len)).min(width - 1);
+    let last = (u64::from(end) * width)
+        .div_ceil(u64::from(len))
+        .clamp(first + 1, width);
+    Some((first as u16, last as u16))
+}
+
+// columns left for the tracks once the y-axis is drawn
+fn track_width(terminal_width: u16) -> u16 {
+    terminal_width.saturating_sub(AXIS_WIDTH)
+}
+
+// start and end labels at the edges of the axis, with the midpoint between them when it fits;
+// each label is only as wide as its digits
+fn generate_and_format_dynamic_label(
+    label_start: u32,
+    label_end: u32,
+    axis_width: u16,
+    with_mid: bool,
+) -> String {
+    let width = axis_width as usize;
+    let start_label = thousands(label_start);
+    let end_label = thousands(label_end);
+    // too narrow for both, the start matters most
+    if start_label.len() + 1 + end_label.len() > width {
+        return start_label;
+    }
+    let mut label = vec![' '; width];
+    label[..start_label.len()].copy_from_slice(&start_label.chars().collect::<Vec<_>>());
+    label[width - end_label.len()..].copy_from_slice(&end_label.chars().collect::<Vec<_>>());
+    if with_mid {
+        // a flipped view has its end left of its start
+        let mid_label = thousands(label_start.min(label_end) + label_start.abs_diff(label_end) / 2);
+        let mid_start = (width - mid_label.len()) / 2;
+        // keep a space to the start and end labels
+        if mid_start > start_label.len() && mid_start + mid_label.len() < width - end_label.len() {
+            label[mid_start..mid_start + mid_label.len()]
+                .copy_from_slice(&mid_label.chars().collect::<Vec<_>>());
+        }
+    }
+    label.into_iter().collect()
+}
+
+// standard base64 with padding, as the clipboard escape sequence expects
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// a coordinate with thousands separators, 2078887 as 2,078,887
+fn thousands(n: u32) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
 }
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use rust_htslib::bcf::{self, Read};
+use std::path::Path;
+
+/// Rough class of a variant, enough to color its marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantKind {
+    /// single base substitution
+    Snv,
+    /// insertion or deletion
+    Indel,
+    /// multi-base substitution, symbolic or breakend allele
+    Other,
+}
+
+/// A variant position inside the viewed region
+#[derive(Debug, Clone, Copy)]
+pub struct Variant {
+    /// 0-based position
+    pub pos: u32,
+    pub kind: VariantKind,
+}
+
+/// Variants of an indexed VCF or BCF starting from 0-based `start` to exclusive `end`.
+///
+/// A contig the file does not know has no variants rather than being an error.
+pub fn fetch_variants<P: AsRef<Path>>(
+    path: P,
+    chrom: &str,
+    start: u32,
+    end: u32,
+) -> Result<Vec<Variant>> {
+    let path = path.as_ref();
+    let mut reader = bcf::IndexedReader::from_path(path)
+        .map_err(|e| anyhow!("failed to open indexed vcf {}: {}", path.display(), e))?;
+    let rid = match reader.header().name2rid(chrom.as_bytes()) {
+        Ok(rid) => rid,
+        Err(_) => return Ok(Vec::new()),
+    };
+    if end <= start {
+        return Ok(Vec::new());
+    }
+    reader.fetch(rid, start as u64, Some(end as u64 - 1))?;
+
+    let mut variants = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        // records overlapping the start but beginning before it are left out
+        let pos = record.pos();
+        if pos < start as i64 || pos >= end as i64 {
+            continue;
+        }
+        variants.push(Variant {
+            pos: pos as u32,
+            kind: variant_kind(&record.alleles()),
+        });
+    }
+    Ok(variants)
+}
+
+// classify by allele lengths, the reference allele first
+fn variant_kind(alleles: &[&[u8]]) -> VariantKind {
+    let Some((reference, alts)) = alleles.split_first() else {
+        return VariantKind::Other;
+    };
+    let symbolic = |allele: &[u8]| {
+        allele.contains(&b'<') || allele.contains(&b'[') || allele.contains(&b']') || allele == b"*"
+    };
+    if alts.is_empty() || alts.iter().any(|alt| symbolic(alt)) {
+        VariantKind::Other
+    } else if alts.iter().any(|alt| alt.len() != reference.len()) {
+        VariantKind::Indel
+    } else if reference.len() == 1 {
+        VariantKind::Snv
+    } else {
+        VariantKind::Other
+    }
+}